@@ -0,0 +1,399 @@
+use std::collections::{HashMap, HashSet};
+use std::mem;
+
+use crate::analysis::Symbol;
+use crate::ast::*;
+use crate::scanner::Span;
+
+#[derive(Debug)]
+pub enum AssignmentWarning {
+    /// A read of a variable that may not have been given a value yet, with the span of its
+    /// declaration
+    Unassigned { span: Span, declaration: Span },
+}
+
+impl AssignmentWarning {
+    pub fn span(&self) -> Span {
+        match self {
+            AssignmentWarning::Unassigned { span, .. } => *span,
+        }
+    }
+}
+
+/// The symbol an identifier was resolved to.
+fn symbol(expr: &Expr) -> Option<usize> {
+    match expr.kind {
+        ExprKind::Identifier { symbol, .. } => symbol,
+        _ => None,
+    }
+}
+
+/// What the analysis needs to know about declarations, gathered before it starts.
+#[derive(Default)]
+struct Declarations {
+    /// The variables that have no value until one is assigned, by symbol. Arrays and records
+    /// are created along with their elements and fields, which are not tracked.
+    variables: HashSet<usize>,
+    /// The named types whose variables have no value until one is assigned
+    empty_types: HashSet<usize>,
+    /// The passing mode of each parameter of each procedure and function, by the routine's symbol
+    parameters: HashMap<usize, Vec<PassingMode>>,
+}
+
+impl Declarations {
+    fn gather<'a>(&mut self, block: &'a Block, variables: &mut Vec<(usize, &'a Type)>) {
+        for stmt in &block.contents {
+            self.gather_stmt(stmt, variables);
+        }
+    }
+
+    fn gather_stmt<'a>(&mut self, stmt: &'a Stmt, variables: &mut Vec<(usize, &'a Type)>) {
+        match &stmt.kind {
+            StmtKind::VariableDecl { name, type_ } => variables.extend(symbol(name).map(|symbol| (symbol, type_))),
+            StmtKind::TypeDecl {
+                name,
+                type_: Type::Enumerated(_) | Type::Pointer(_),
+            } => self.empty_types.extend(symbol(name)),
+            StmtKind::ClassDecl { name, members, .. } => {
+                self.empty_types.extend(symbol(name));
+                for member in members {
+                    if let Member::Method { decl, .. } = member {
+                        self.gather_stmt(decl, variables);
+                    }
+                }
+            }
+            StmtKind::ProcedureDecl { name, params, body } | StmtKind::FunctionDecl { name, params, body, .. } => {
+                if let Some(symbol) = symbol(name) {
+                    let modes = params.iter().flatten().map(|param| param.mode).collect();
+                    self.parameters.insert(symbol, modes);
+                }
+                self.gather(body, variables);
+            }
+            StmtKind::If {
+                then_branch,
+                else_branch,
+                ..
+            } => {
+                self.gather(then_branch, variables);
+                if let Some(else_branch) = else_branch {
+                    self.gather(else_branch, variables);
+                }
+            }
+            StmtKind::CaseOf { cases, otherwise, .. } => {
+                for (_, body) in cases {
+                    self.gather(body, variables);
+                }
+                if let Some(otherwise) = otherwise {
+                    self.gather(otherwise, variables);
+                }
+            }
+            StmtKind::ForLoop { body, .. } | StmtKind::RepeatUntil { body, .. } | StmtKind::While { body, .. } => {
+                self.gather(body, variables);
+            }
+            _ => {}
+        }
+    }
+
+    /// Whether a variable of type `type_` starts without a value.
+    fn starts_empty(&self, type_: &Type) -> bool {
+        match type_ {
+            Type::Primitive(_) | Type::Pointer(_) | Type::Enumerated(_) => true,
+            Type::Named(name) => symbol(name).is_some_and(|symbol| self.empty_types.contains(&symbol)),
+            Type::Array(_) => false,
+        }
+    }
+}
+
+struct AssignmentChecker<'a> {
+    symbols: &'a [Symbol],
+    declarations: Declarations,
+    /// The variables that have been given a value on every path to the current statement
+    assigned: HashSet<usize>,
+    warnings: Vec<AssignmentWarning>,
+}
+
+impl AssignmentChecker<'_> {
+    fn check_block(&mut self, block: &Block) {
+        for stmt in &block.contents {
+            self.check_stmt(stmt);
+        }
+    }
+
+    /// Checks a block that may or may not run, returning the variables assigned by its end and
+    /// leaving those assigned before it unchanged.
+    fn check_branch(&mut self, block: &Block) -> HashSet<usize> {
+        let before = self.assigned.clone();
+        self.check_block(block);
+        mem::replace(&mut self.assigned, before)
+    }
+
+    /// Checks the body of a procedure, function or method.
+    fn check_routine(&mut self, body: &Block) {
+        // Globals may be given values before the routine is called, so only the routine's own
+        // variables start without one, once they are declared
+        let outer = mem::replace(&mut self.assigned, self.declarations.variables.clone());
+        self.check_block(body);
+        self.assigned = outer;
+    }
+
+    fn check_stmt(&mut self, stmt: &Stmt) {
+        match &stmt.kind {
+            StmtKind::ProcedureDecl { body, .. } | StmtKind::FunctionDecl { body, .. } => self.check_routine(body),
+            StmtKind::ClassDecl { members, .. } => {
+                for member in members {
+                    if let Member::Method { decl, .. } = member {
+                        self.check_stmt(decl);
+                    }
+                }
+            }
+            StmtKind::VariableDecl { name, .. } => {
+                if let Some(symbol) = symbol(name) {
+                    self.assigned.remove(&symbol);
+                }
+            }
+            StmtKind::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.read(condition);
+                let then_assigned = self.check_branch(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.check_block(else_branch);
+                }
+                self.assigned.retain(|symbol| then_assigned.contains(symbol));
+            }
+            StmtKind::CaseOf {
+                condition,
+                cases,
+                otherwise,
+            } => {
+                self.read(condition);
+                let cases_assigned: Vec<_> = cases.iter().map(|(_, body)| self.check_branch(body)).collect();
+                if let Some(otherwise) = otherwise {
+                    self.check_block(otherwise);
+                }
+                for case_assigned in cases_assigned {
+                    self.assigned.retain(|symbol| case_assigned.contains(symbol));
+                }
+            }
+            // The bodies of these loops may not run at all
+            StmtKind::ForLoop {
+                target,
+                start,
+                end,
+                step,
+                body,
+            } => {
+                self.read(start);
+                self.read(end);
+                if let Some(step) = step {
+                    self.read(step);
+                }
+                self.assign(target);
+                self.check_branch(body);
+            }
+            StmtKind::While { condition, body } => {
+                self.read(condition);
+                self.check_branch(body);
+            }
+            StmtKind::RepeatUntil { body, condition } => {
+                self.check_block(body);
+                self.read(condition);
+            }
+            StmtKind::Input(targets) => targets.iter().for_each(|target| self.assign(target)),
+            StmtKind::Output(values) => values.iter().for_each(|value| self.read(value)),
+            StmtKind::Return(value) => {
+                self.read(value);
+                // Nothing after a RETURN runs, so it should not limit what is assigned where
+                // paths meet
+                self.assigned = self.declarations.variables.clone();
+            }
+            StmtKind::FileOpen { file, .. } | StmtKind::FileClose { file } => self.read(file),
+            StmtKind::FileRead { file, target } | StmtKind::FileGetRecord { file, target } => {
+                self.read(file);
+                self.assign(target);
+            }
+            StmtKind::FileWrite { file, value: other }
+            | StmtKind::FileSeek { file, address: other }
+            | StmtKind::FilePutRecord { file, source: other } => {
+                self.read(file);
+                self.read(other);
+            }
+            StmtKind::Procedure { name, args } => self.call(name, args.as_deref().unwrap_or_default()),
+            StmtKind::Assignment { target, value } => {
+                self.read(value);
+                self.assign(target);
+            }
+            StmtKind::ConstantDecl { .. } | StmtKind::RecordDecl { .. } | StmtKind::TypeDecl { .. } => {}
+        }
+    }
+
+    /// Gives a value to `target`, reading whatever is needed to find where it is stored.
+    fn assign(&mut self, target: &Expr) {
+        match &target.kind {
+            ExprKind::Identifier { symbol, .. } => self.assigned.extend(*symbol),
+            _ => self.read(target),
+        }
+    }
+
+    fn call(&mut self, callee: &Expr, args: &[Expr]) {
+        let modes = match &callee.kind {
+            ExprKind::Identifier { symbol, .. } => {
+                symbol.and_then(|symbol| self.declarations.parameters.get(&symbol)).cloned().unwrap_or_default()
+            }
+            // Which method is called depends on the object, so any argument may be passed by
+            // reference
+            ExprKind::FieldAccess { object, .. } => {
+                self.read(object);
+                vec![PassingMode::ByReference; args.len()]
+            }
+            _ => Vec::new(),
+        };
+        for (index, arg) in args.iter().enumerate() {
+            match (modes.get(index), &arg.kind) {
+                // A variable passed by reference may be given its value by the routine
+                (Some(PassingMode::ByReference), ExprKind::Identifier { .. }) => self.assign(arg),
+                _ => self.read(arg),
+            }
+        }
+    }
+
+    fn read(&mut self, expr: &Expr) {
+        match &expr.kind {
+            ExprKind::Identifier {
+                symbol: Some(symbol), ..
+            } => {
+                // The variable is counted as assigned from here on, so it is only reported once
+                if self.declarations.variables.contains(symbol) && self.assigned.insert(*symbol) {
+                    self.warnings.push(AssignmentWarning::Unassigned {
+                        span: expr.span,
+                        declaration: self.symbols[*symbol].span,
+                    });
+                }
+            }
+            ExprKind::Binary { left, right, .. } => {
+                self.read(left);
+                self.read(right);
+            }
+            ExprKind::Unary { right, .. } => self.read(right),
+            ExprKind::FunctionCall { function, args } => self.call(function, args),
+            ExprKind::ArrayIndex { array, indexes } => {
+                self.read(array);
+                indexes.iter().for_each(|index| self.read(index));
+            }
+            ExprKind::FieldAccess { object, .. } => self.read(object),
+            ExprKind::Dereference { pointer } => self.read(pointer),
+            // A pointer to a variable may be used to give it a value
+            ExprKind::AddressOf { target } => self.assign(target),
+            ExprKind::New { args, .. } => args.iter().for_each(|arg| self.read(arg)),
+            ExprKind::Identifier { symbol: None, .. } | ExprKind::Super | ExprKind::Literal(_) => {}
+        }
+    }
+}
+
+/// Finds reads of variables that may not have been given a value on every path leading to them.
+/// `symbols` are those found by `resolve`, which must already have been run on `program`.
+pub fn check_assignments(program: &Program, symbols: &[Symbol]) -> Vec<AssignmentWarning> {
+    let mut declarations = Declarations::default();
+    let mut variables = Vec::new();
+    declarations.gather(&program.body, &mut variables);
+    declarations.variables = variables
+        .into_iter()
+        .filter(|(_, type_)| declarations.starts_empty(type_))
+        .map(|(symbol, _)| symbol)
+        .collect();
+
+    let mut checker = AssignmentChecker {
+        symbols,
+        declarations,
+        assigned: HashSet::new(),
+        warnings: Vec::new(),
+    };
+    checker.check_block(&program.body);
+    checker.warnings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::resolve;
+    use crate::parser;
+
+    /// The lines of the reads reported in `source`.
+    fn check(source: &str) -> Vec<u32> {
+        let (mut program, errors) = parser::parse_program(source);
+        assert!(errors.is_empty(), "{errors:?}");
+        let (symbols, errors) = resolve(&mut program);
+        assert!(errors.is_empty(), "{errors:?}");
+        let warnings = check_assignments(&program, &symbols);
+        warnings.iter().map(|warning| warning.span().start.line).collect()
+    }
+
+    #[test]
+    fn branches_and_loops() {
+        let source = "
+            DECLARE A : INTEGER
+            DECLARE B : INTEGER
+            DECLARE C : INTEGER
+            DECLARE D : INTEGER
+            DECLARE E : INTEGER
+            DECLARE F : INTEGER
+            IF TRUE THEN
+                A <- 1
+                B <- 1
+            ELSE
+                A <- 2
+            ENDIF
+            CASE OF A
+                1 : C <- 1
+                OTHERWISE : C <- 2
+            ENDCASE
+            CASE OF A
+                1 : D <- 1
+            ENDCASE
+            WHILE A < 5 DO
+                E <- A
+                A <- E + 1
+            ENDWHILE
+            REPEAT
+                F <- 1
+            UNTIL F = 1
+            FOR I <- 1 TO 3
+            NEXT I
+            OUTPUT A, B, C, D, E, F, I, B
+        ";
+        // B, D and E are each reported once, in the last line
+        assert_eq!(check(source), [30, 30, 30]);
+    }
+
+    #[test]
+    fn routines() {
+        let source = "
+            DECLARE Total : INTEGER
+            DECLARE Count : INTEGER
+            DECLARE Scores : ARRAY[1:3] OF INTEGER
+            DECLARE Extra : INTEGER
+            PROCEDURE Fill(Limit : INTEGER, BYREF Value : INTEGER)
+                Value <- Total + Limit
+            ENDPROCEDURE
+            FUNCTION Sign(N : INTEGER) RETURNS INTEGER
+                DECLARE Result : INTEGER
+                IF N < 0 THEN
+                    RETURN -1
+                ELSE
+                    Result <- 1
+                ENDIF
+                RETURN Result
+            ENDFUNCTION
+            FUNCTION Broken RETURNS INTEGER
+                DECLARE Result : INTEGER
+                RETURN Result
+            ENDFUNCTION
+            CALL Fill(Extra, Count)
+            INPUT Extra
+            OUTPUT Count, Scores[1], Extra, Sign(Total)
+        ";
+        assert_eq!(check(source), [20, 22, 24]);
+    }
+}
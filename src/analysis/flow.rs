@@ -0,0 +1,431 @@
+use std::collections::HashSet;
+
+use crate::ast::*;
+use crate::scanner::Span;
+
+#[derive(Debug)]
+pub enum FlowError {
+    /// A function that can reach its end without returning a value, given by its name
+    MissingReturn(Span),
+    /// A RETURN in a procedure or in the main program
+    ReturnOutsideFunction(Span),
+}
+
+impl FlowError {
+    pub fn span(&self) -> Span {
+        match self {
+            FlowError::MissingReturn(span) | FlowError::ReturnOutsideFunction(span) => *span,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum FlowWarning {
+    /// A statement that follows a RETURN, so can never be run
+    Unreachable(Span),
+    /// The condition of a REPEAT or WHILE loop that reads no variable the loop assigns to
+    UnchangingCondition(Span),
+}
+
+impl FlowWarning {
+    pub fn span(&self) -> Span {
+        match self {
+            FlowWarning::Unreachable(span) | FlowWarning::UnchangingCondition(span) => *span,
+        }
+    }
+}
+
+/// The kind of code a statement is part of.
+#[derive(Clone, Copy, PartialEq)]
+enum Context {
+    Program,
+    Procedure,
+    Function,
+}
+
+/// What the statements in the body of a loop may change.
+#[derive(Default)]
+struct Effects {
+    /// The variables assigned to, by handle
+    assigned: HashSet<usize>,
+    /// Whether the body may change variables it does not name, by calling a routine or
+    /// assigning through a pointer
+    unknown: bool,
+    /// Whether the body can leave the loop through a RETURN
+    returns: bool,
+}
+
+/// The variable an assignment to `target` changes, or `None` if it could be any variable.
+fn assigned_variable(target: &Expr) -> Option<usize> {
+    match &target.kind {
+        ExprKind::Identifier { handle, .. } => Some(*handle),
+        ExprKind::ArrayIndex { array: inner, .. } | ExprKind::FieldAccess { object: inner, .. } => {
+            assigned_variable(inner)
+        }
+        _ => None,
+    }
+}
+
+/// Whether `expr` calls a routine other than a builtin, which may change any variable.
+fn calls_routine(expr: &Expr) -> bool {
+    match &expr.kind {
+        ExprKind::FunctionCall { function, args } => {
+            !matches!(function.kind, ExprKind::Identifier { handle, .. } if Builtin::from_handle(handle).is_some())
+                || args.iter().any(calls_routine)
+        }
+        ExprKind::New { .. } => true,
+        ExprKind::Binary { left, right, .. } => calls_routine(left) || calls_routine(right),
+        ExprKind::ArrayIndex { array, indexes } => calls_routine(array) || indexes.iter().any(calls_routine),
+        ExprKind::Unary { right: inner, .. }
+        | ExprKind::FieldAccess { object: inner, .. }
+        | ExprKind::Dereference { pointer: inner }
+        | ExprKind::AddressOf { target: inner } => calls_routine(inner),
+        ExprKind::Super | ExprKind::Identifier { .. } | ExprKind::Literal(_) => false,
+    }
+}
+
+/// Adds the variables `expr` reads to `reads`. Returns false if its value can change without
+/// any of them being assigned, as when it calls a function.
+fn variables_read(expr: &Expr, reads: &mut HashSet<usize>) -> bool {
+    match &expr.kind {
+        ExprKind::Identifier { handle, .. } => {
+            reads.insert(*handle);
+            true
+        }
+        ExprKind::FunctionCall { .. } | ExprKind::New { .. } => false,
+        ExprKind::Binary { left, right, .. } => variables_read(left, reads) && variables_read(right, reads),
+        ExprKind::ArrayIndex { array, indexes } => {
+            variables_read(array, reads) && indexes.iter().all(|index| variables_read(index, reads))
+        }
+        // Fields are part of the variable that holds them
+        ExprKind::Unary { right: inner, .. }
+        | ExprKind::FieldAccess { object: inner, .. }
+        | ExprKind::AddressOf { target: inner } => variables_read(inner, reads),
+        // What a pointer points to can be changed through other pointers
+        ExprKind::Dereference { .. } => false,
+        ExprKind::Super | ExprKind::Literal(_) => true,
+    }
+}
+
+impl Effects {
+    fn assign(&mut self, target: &Expr) {
+        match assigned_variable(target) {
+            Some(handle) => drop(self.assigned.insert(handle)),
+            None => self.unknown = true,
+        }
+    }
+
+    fn read(&mut self, expr: &Expr) {
+        self.unknown |= calls_routine(expr);
+    }
+
+    fn block(&mut self, block: &Block) {
+        for stmt in &block.contents {
+            self.stmt(stmt);
+        }
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        match &stmt.kind {
+            StmtKind::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.read(condition);
+                self.block(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.block(else_branch);
+                }
+            }
+            StmtKind::CaseOf {
+                condition,
+                cases,
+                otherwise,
+            } => {
+                self.read(condition);
+                for (_, body) in cases {
+                    self.block(body);
+                }
+                if let Some(otherwise) = otherwise {
+                    self.block(otherwise);
+                }
+            }
+            StmtKind::ForLoop {
+                target,
+                start,
+                end,
+                step,
+                body,
+            } => {
+                self.assign(target);
+                self.read(start);
+                self.read(end);
+                if let Some(step) = step {
+                    self.read(step);
+                }
+                self.block(body);
+            }
+            StmtKind::RepeatUntil { body, condition } | StmtKind::While { condition, body } => {
+                self.read(condition);
+                self.block(body);
+            }
+            StmtKind::VariableDecl { name, .. } | StmtKind::ConstantDecl { name, .. } => self.assign(name),
+            StmtKind::Input(targets) => targets.iter().for_each(|target| self.assign(target)),
+            StmtKind::Output(values) => values.iter().for_each(|value| self.read(value)),
+            StmtKind::Return(value) => {
+                self.read(value);
+                self.returns = true;
+            }
+            StmtKind::FileRead { file, target } | StmtKind::FileGetRecord { file, target } => {
+                self.read(file);
+                self.assign(target);
+            }
+            StmtKind::FileOpen { file, .. } | StmtKind::FileClose { file } => self.read(file),
+            StmtKind::FileWrite { file, value: other }
+            | StmtKind::FileSeek { file, address: other }
+            | StmtKind::FilePutRecord { file, source: other } => {
+                self.read(file);
+                self.read(other);
+            }
+            // A procedure can change globals and the arguments it takes by reference
+            StmtKind::Procedure { .. } => self.unknown = true,
+            StmtKind::Assignment { target, value } => {
+                self.read(value);
+                self.assign(target);
+            }
+            StmtKind::ProcedureDecl { .. }
+            | StmtKind::FunctionDecl { .. }
+            | StmtKind::RecordDecl { .. }
+            | StmtKind::ClassDecl { .. }
+            | StmtKind::TypeDecl { .. } => {}
+        }
+    }
+}
+
+struct FlowChecker {
+    errors: Vec<FlowError>,
+    warnings: Vec<FlowWarning>,
+}
+
+impl FlowChecker {
+    /// Checks the statements in `block`, returning whether it returns on every path.
+    fn check_block(&mut self, block: &Block, context: Context) -> bool {
+        let mut returns = false;
+        let mut reported = false;
+        for stmt in &block.contents {
+            // Only the first unreachable statement is reported, as the rest follow from it
+            if returns && !reported {
+                self.warnings.push(FlowWarning::Unreachable(stmt.span));
+                reported = true;
+            }
+            returns |= self.check_stmt(stmt, context);
+        }
+        returns
+    }
+
+    /// Checks a statement, returning whether it returns on every path.
+    fn check_stmt(&mut self, stmt: &Stmt, context: Context) -> bool {
+        match &stmt.kind {
+            StmtKind::Return(_) => {
+                if context != Context::Function {
+                    self.errors.push(FlowError::ReturnOutsideFunction(stmt.span));
+                }
+                true
+            }
+            StmtKind::If {
+                then_branch,
+                else_branch,
+                ..
+            } => {
+                let then_returns = self.check_block(then_branch, context);
+                let else_returns = else_branch
+                    .as_ref()
+                    .is_some_and(|else_branch| self.check_block(else_branch, context));
+                then_returns && else_returns
+            }
+            StmtKind::CaseOf { cases, otherwise, .. } => {
+                let mut returns = true;
+                for (_, body) in cases {
+                    returns &= self.check_block(body, context);
+                }
+                match otherwise {
+                    Some(otherwise) => self.check_block(otherwise, context) && returns,
+                    None => false,
+                }
+            }
+            // These loops may not run at all
+            StmtKind::ForLoop { body, .. } => {
+                self.check_block(body, context);
+                false
+            }
+            StmtKind::While { condition, body } => {
+                self.check_block(body, context);
+                self.check_condition(condition, body);
+                false
+            }
+            // The body of a REPEAT loop always runs at least once
+            StmtKind::RepeatUntil { body, condition } => {
+                let returns = self.check_block(body, context);
+                self.check_condition(condition, body);
+                returns
+            }
+            StmtKind::ProcedureDecl { body, .. } => {
+                self.check_block(body, Context::Procedure);
+                false
+            }
+            StmtKind::FunctionDecl { name, body, .. } => {
+                if !self.check_block(body, Context::Function) {
+                    self.errors.push(FlowError::MissingReturn(name.span));
+                }
+                false
+            }
+            StmtKind::ClassDecl { members, .. } => {
+                for member in members {
+                    if let Member::Method { decl, .. } = member {
+                        self.check_stmt(decl, context);
+                    }
+                }
+                false
+            }
+            _ => false,
+        }
+    }
+
+    /// Warns about a loop condition that no statement in the loop can change.
+    fn check_condition(&mut self, condition: &Expr, body: &Block) {
+        let mut reads = HashSet::new();
+        if !variables_read(condition, &mut reads) {
+            return;
+        }
+        let mut effects = Effects::default();
+        effects.block(body);
+        if !effects.unknown && !effects.returns && reads.is_disjoint(&effects.assigned) {
+            self.warnings.push(FlowWarning::UnchangingCondition(condition.span));
+        }
+    }
+}
+
+/// Checks that functions return a value on every path, that RETURN is only used in functions,
+/// and that loops can end and every statement can be reached.
+pub fn check_flow(program: &Program) -> (Vec<FlowError>, Vec<FlowWarning>) {
+    let mut checker = FlowChecker {
+        errors: Vec::new(),
+        warnings: Vec::new(),
+    };
+    checker.check_block(&program.body, Context::Program);
+    (checker.errors, checker.warnings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser;
+
+    fn check(source: &str) -> (Vec<FlowError>, Vec<FlowWarning>) {
+        let (program, errors) = parser::parse_program(source);
+        assert!(errors.is_empty(), "{errors:?}");
+        check_flow(&program)
+    }
+
+    #[test]
+    fn returns() {
+        let source = "
+            FUNCTION Sign(N : INTEGER) RETURNS INTEGER
+                IF N < 0 THEN
+                    RETURN -1
+                ELSE
+                    RETURN 1
+                ENDIF
+            ENDFUNCTION
+            FUNCTION Grade(Mark : INTEGER) RETURNS CHAR
+                CASE OF Mark
+                    70 TO 100 : RETURN 'A'
+                    OTHERWISE : RETURN 'U'
+                ENDCASE
+            ENDFUNCTION
+            FUNCTION First(N : INTEGER) RETURNS INTEGER
+                REPEAT
+                    RETURN N
+                UNTIL TRUE
+            ENDFUNCTION
+            FUNCTION Positive(N : INTEGER) RETURNS BOOLEAN
+                IF N > 0 THEN
+                    RETURN TRUE
+                ENDIF
+            ENDFUNCTION
+            FUNCTION Largest(N : INTEGER) RETURNS INTEGER
+                WHILE N > 10 DO
+                    RETURN N
+                ENDWHILE
+            ENDFUNCTION
+            PROCEDURE Show(N : INTEGER)
+                RETURN N
+            ENDPROCEDURE
+            RETURN 0
+        ";
+        let (errors, _) = check(source);
+        let lines: Vec<_> = errors.iter().map(|error| error.span().start.line).collect();
+        assert_eq!(lines, [20, 25, 31, 33], "{errors:?}");
+        assert!(matches!(
+            errors[..],
+            [
+                FlowError::MissingReturn(_),
+                FlowError::MissingReturn(_),
+                FlowError::ReturnOutsideFunction(_),
+                FlowError::ReturnOutsideFunction(_),
+            ]
+        ));
+    }
+
+    #[test]
+    fn unreachable() {
+        let source = "
+            FUNCTION Double(N : INTEGER) RETURNS INTEGER
+                IF N = 0 THEN
+                    RETURN 0
+                    OUTPUT \"zero\"
+                    OUTPUT \"again\"
+                ENDIF
+                RETURN N * 2
+                OUTPUT N
+            ENDFUNCTION
+        ";
+        let (errors, warnings) = check(source);
+        assert!(errors.is_empty(), "{errors:?}");
+        let lines: Vec<_> = warnings.iter().map(|warning| warning.span().start.line).collect();
+        assert_eq!(lines, [5, 9]);
+    }
+
+    #[test]
+    fn unchanging_conditions() {
+        let source = "
+            DECLARE Count : INTEGER
+            DECLARE Total : INTEGER
+            DECLARE Data : ARRAY[1:3] OF INTEGER
+            Count <- 0
+            WHILE Count < 10 DO
+                Total <- Total + 1
+            ENDWHILE
+            WHILE Count < 10 DO
+                Count <- Count + 1
+            ENDWHILE
+            REPEAT
+                Data[Count] <- 0
+                INPUT Total
+            UNTIL Data[1] = Total
+            REPEAT
+                OUTPUT Count
+            UNTIL FALSE
+            WHILE Count > 0 DO
+                CALL Decrease(Count)
+            ENDWHILE
+            WHILE NOT EOF(\"data.txt\") DO
+                OUTPUT Count
+            ENDWHILE
+        ";
+        let (_, warnings) = check(source);
+        let lines: Vec<_> = warnings.iter().map(|warning| warning.span().start.line).collect();
+        assert_eq!(lines, [6, 18], "{warnings:?}");
+    }
+}
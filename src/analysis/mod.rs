@@ -0,0 +1,11 @@
+//! Checks made on a parsed program before it is run.
+
+mod assignment;
+mod flow;
+mod resolve;
+mod types;
+
+pub use assignment::*;
+pub use flow::*;
+pub use resolve::*;
+pub use types::*;
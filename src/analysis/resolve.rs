@@ -0,0 +1,611 @@
+use std::collections::HashMap;
+
+use crate::ast::*;
+use crate::scanner::Span;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SymbolKind {
+    Variable,
+    Constant,
+    Parameter(PassingMode),
+    /// An attribute of a class, usable by name inside its methods
+    Attribute,
+    /// A procedure, function or method
+    Routine,
+    /// A record, enumerated, pointer or class type
+    Type,
+}
+
+/// Where a symbol can be used from.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Scope {
+    Global,
+    /// Inside the routine or method with the given symbol
+    Routine(usize),
+    /// Inside the methods of the class with the given symbol
+    Class(usize),
+}
+
+/// A name declared in a program.
+#[derive(Clone, Debug)]
+pub struct Symbol {
+    pub handle: usize,
+    pub kind: SymbolKind,
+    pub scope: Scope,
+    /// The span of the name where it is declared
+    pub span: Span,
+}
+
+#[derive(Debug)]
+pub enum ResolveError {
+    UndeclaredVariable(Span),
+    UndefinedRoutine(Span),
+    UndefinedType(Span),
+    /// A name declared a second time in the same scope
+    Redeclared { span: Span, previous: Span },
+    AssignToConstant { span: Span, declaration: Span },
+}
+
+impl ResolveError {
+    pub fn span(&self) -> Span {
+        match self {
+            ResolveError::UndeclaredVariable(span)
+            | ResolveError::UndefinedRoutine(span)
+            | ResolveError::UndefinedType(span)
+            | ResolveError::Redeclared { span, .. }
+            | ResolveError::AssignToConstant { span, .. } => *span,
+        }
+    }
+}
+
+/// The members of a class, by handle, and the class it inherits from.
+struct ClassScope {
+    parent: Option<usize>,
+    attributes: Bindings,
+    methods: Bindings,
+}
+
+/// Where each name is bound, by handle, along with the symbol it is bound to.
+type Bindings = HashMap<usize, usize>;
+
+/// The set of bindings a name is declared in. Variables, routines and types can share a name.
+#[derive(Clone, Copy)]
+enum Namespace {
+    /// Variables, constants and parameters of the current scope
+    Values,
+    Routines,
+    Types,
+    /// The attributes of the class with the given handle
+    Attributes(usize),
+    /// The methods of the class with the given handle
+    Methods(usize),
+}
+
+struct Resolver {
+    symbols: Vec<Symbol>,
+    globals: Bindings,
+    routines: Bindings,
+    types: Bindings,
+    classes: HashMap<usize, ClassScope>,
+    /// The parameters and variables of the routine being resolved, if any
+    locals: Option<Bindings>,
+    /// The class the method being resolved belongs to
+    class: Option<usize>,
+    scope: Scope,
+    errors: Vec<ResolveError>,
+}
+
+fn handle(expr: &Expr) -> Option<usize> {
+    match expr.kind {
+        ExprKind::Identifier { handle, .. } => Some(handle),
+        _ => None,
+    }
+}
+
+fn annotate(expr: &mut Expr, symbol: usize) {
+    if let ExprKind::Identifier { symbol: slot, .. } = &mut expr.kind {
+        *slot = Some(symbol);
+    }
+}
+
+impl Resolver {
+    fn new() -> Self {
+        Resolver {
+            symbols: Vec::new(),
+            globals: Bindings::new(),
+            routines: Bindings::new(),
+            types: Bindings::new(),
+            classes: HashMap::new(),
+            locals: None,
+            class: None,
+            scope: Scope::Global,
+            errors: Vec::new(),
+        }
+    }
+
+    fn bindings(&mut self, namespace: Namespace) -> &mut Bindings {
+        match namespace {
+            Namespace::Values => self.locals.as_mut().unwrap_or(&mut self.globals),
+            Namespace::Routines => &mut self.routines,
+            Namespace::Types => &mut self.types,
+            Namespace::Attributes(class) => &mut self.classes.get_mut(&class).unwrap().attributes,
+            Namespace::Methods(class) => &mut self.classes.get_mut(&class).unwrap().methods,
+        }
+    }
+
+    /// Adds a symbol for `name` to a namespace, reporting it if the name is already bound there.
+    fn bind(&mut self, name: &mut Expr, kind: SymbolKind, scope: Scope, namespace: Namespace) -> usize {
+        let Some(handle) = handle(name) else {
+            return usize::MAX;
+        };
+        let symbol = self.symbols.len();
+        self.symbols.push(Symbol {
+            handle,
+            kind,
+            scope,
+            span: name.span,
+        });
+        annotate(name, symbol);
+        if let Some(previous) = self.bindings(namespace).insert(handle, symbol) {
+            let previous = self.symbols[previous].span;
+            self.errors.push(ResolveError::Redeclared { span: name.span, previous });
+        }
+        symbol
+    }
+
+    /// Declares a variable, constant or parameter in the current scope.
+    fn declare(&mut self, name: &mut Expr, kind: SymbolKind) {
+        self.bind(name, kind, self.scope, Namespace::Values);
+    }
+
+    /// Declares the types and routines in `block`, which can be used before the point where
+    /// they are declared, along with the members of classes.
+    fn declare_hoisted(&mut self, block: &mut Block) {
+        for stmt in &mut block.contents {
+            match &mut stmt.kind {
+                StmtKind::RecordDecl { name, .. } | StmtKind::TypeDecl { name, .. } => {
+                    self.bind(name, SymbolKind::Type, Scope::Global, Namespace::Types);
+                }
+                StmtKind::ProcedureDecl { name, .. } | StmtKind::FunctionDecl { name, .. } => {
+                    self.bind(name, SymbolKind::Routine, Scope::Global, Namespace::Routines);
+                }
+                StmtKind::ClassDecl { name, parent, members } => {
+                    let class = self.bind(name, SymbolKind::Type, Scope::Global, Namespace::Types);
+                    let Some(class_handle) = handle(name) else { continue };
+                    self.classes.insert(
+                        class_handle,
+                        ClassScope {
+                            parent: parent.as_ref().and_then(handle),
+                            attributes: Bindings::new(),
+                            methods: Bindings::new(),
+                        },
+                    );
+                    for member in members {
+                        match member {
+                            Member::Attribute { field, .. } => {
+                                let namespace = Namespace::Attributes(class_handle);
+                                self.bind(&mut field.name, SymbolKind::Attribute, Scope::Class(class), namespace);
+                            }
+                            Member::Method { decl, .. } => {
+                                if let StmtKind::ProcedureDecl { name, .. } | StmtKind::FunctionDecl { name, .. } =
+                                    &mut decl.kind
+                                {
+                                    let namespace = Namespace::Methods(class_handle);
+                                    self.bind(name, SymbolKind::Routine, Scope::Class(class), namespace);
+                                }
+                            }
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// The classes `class` is made up of: itself, then each class it inherits from in turn.
+    fn lineage(&self, class: usize) -> Vec<&ClassScope> {
+        let mut lineage: Vec<&ClassScope> = Vec::new();
+        let mut current = Some(class);
+        while let Some(scope) = current.and_then(|class| self.classes.get(&class)) {
+            if lineage.iter().any(|other| std::ptr::eq(*other, scope)) {
+                break;
+            }
+            lineage.push(scope);
+            current = scope.parent;
+        }
+        lineage
+    }
+
+    /// Finds the variable, constant or parameter that `handle` refers to, looking in the current
+    /// routine, then the attributes of the current class, then the globals.
+    fn lookup(&self, handle: usize) -> Option<usize> {
+        if let Some(&symbol) = self.locals.as_ref().and_then(|locals| locals.get(&handle)) {
+            return Some(symbol);
+        }
+        let attribute = self.class.and_then(|class| {
+            self.lineage(class).into_iter().find_map(|scope| scope.attributes.get(&handle).copied())
+        });
+        attribute.or_else(|| self.globals.get(&handle).copied())
+    }
+
+    fn lookup_routine(&self, handle: usize) -> Option<usize> {
+        // Inside a method, a bare name can refer to another method of the same object
+        let method = self.class.and_then(|class| {
+            self.lineage(class).into_iter().find_map(|scope| scope.methods.get(&handle).copied())
+        });
+        method.or_else(|| self.routines.get(&handle).copied())
+    }
+
+    fn resolve_routine(&mut self, decl: &mut Stmt, class: Option<usize>) {
+        let (name, params, return_type, body) = match &mut decl.kind {
+            StmtKind::ProcedureDecl { name, params, body } => (name, params, None, body),
+            StmtKind::FunctionDecl {
+                name,
+                params,
+                return_type,
+                body,
+            } => (name, params, Some(return_type), body),
+            _ => return,
+        };
+        let ExprKind::Identifier { symbol: Some(symbol), .. } = name.kind else {
+            return;
+        };
+        self.locals = Some(Bindings::new());
+        self.class = class;
+        self.scope = Scope::Routine(symbol);
+        for param in params.iter_mut().flatten() {
+            self.resolve_type(&mut param.type_);
+            self.declare(&mut param.name, SymbolKind::Parameter(param.mode));
+        }
+        if let Some(return_type) = return_type {
+            self.resolve_type(return_type);
+        }
+        self.resolve_block(body);
+        self.locals = None;
+        self.class = None;
+        self.scope = Scope::Global;
+    }
+
+    fn resolve_block(&mut self, block: &mut Block) {
+        for stmt in &mut block.contents {
+            self.resolve_stmt(stmt);
+        }
+    }
+
+    fn resolve_stmt(&mut self, stmt: &mut Stmt) {
+        match &mut stmt.kind {
+            // Routine bodies are resolved once every global has been declared
+            StmtKind::ProcedureDecl { .. } | StmtKind::FunctionDecl { .. } => {}
+            StmtKind::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.resolve_expr(condition);
+                self.resolve_block(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.resolve_block(else_branch);
+                }
+            }
+            StmtKind::CaseOf {
+                condition,
+                cases,
+                otherwise,
+            } => {
+                self.resolve_expr(condition);
+                for (_, body) in cases {
+                    self.resolve_block(body);
+                }
+                if let Some(otherwise) = otherwise {
+                    self.resolve_block(otherwise);
+                }
+            }
+            StmtKind::ForLoop {
+                target,
+                start,
+                end,
+                step,
+                body,
+            } => {
+                self.resolve_expr(start);
+                self.resolve_expr(end);
+                if let Some(step) = step {
+                    self.resolve_expr(step);
+                }
+                // Loop counters are commonly used without being declared
+                if handle(target).is_some_and(|handle| self.lookup(handle).is_none()) {
+                    self.declare(target, SymbolKind::Variable);
+                }
+                self.resolve_target(target);
+                self.resolve_block(body);
+            }
+            StmtKind::RepeatUntil { body, condition } => {
+                self.resolve_block(body);
+                self.resolve_expr(condition);
+            }
+            StmtKind::While { condition, body } => {
+                self.resolve_expr(condition);
+                self.resolve_block(body);
+            }
+            StmtKind::VariableDecl { name, type_ } => {
+                self.resolve_type(type_);
+                self.declare(name, SymbolKind::Variable);
+            }
+            StmtKind::ConstantDecl { name, .. } => self.declare(name, SymbolKind::Constant),
+            StmtKind::RecordDecl { fields, .. } => {
+                for field in fields {
+                    self.resolve_type(&mut field.type_);
+                }
+            }
+            StmtKind::ClassDecl { parent, members, .. } => {
+                if let Some(parent) = parent {
+                    self.resolve_type_name(parent);
+                }
+                for member in members {
+                    if let Member::Attribute { field, .. } = member {
+                        self.resolve_type(&mut field.type_);
+                    }
+                }
+            }
+            StmtKind::TypeDecl {
+                type_: Type::Enumerated(values),
+                ..
+            } => {
+                for value in values {
+                    self.declare(value, SymbolKind::Constant);
+                }
+            }
+            StmtKind::TypeDecl { type_, .. } => self.resolve_type(type_),
+            StmtKind::Input(targets) => {
+                for target in targets {
+                    self.resolve_target(target);
+                }
+            }
+            StmtKind::Output(values) => {
+                for value in values {
+                    self.resolve_expr(value);
+                }
+            }
+            StmtKind::Return(value) => self.resolve_expr(value),
+            StmtKind::FileOpen { file, .. } | StmtKind::FileClose { file } => self.resolve_expr(file),
+            StmtKind::FileRead { file, target } | StmtKind::FileGetRecord { file, target } => {
+                self.resolve_expr(file);
+                self.resolve_target(target);
+            }
+            StmtKind::FileWrite { file, value: other }
+            | StmtKind::FileSeek { file, address: other }
+            | StmtKind::FilePutRecord { file, source: other } => {
+                self.resolve_expr(file);
+                self.resolve_expr(other);
+            }
+            StmtKind::Procedure { name, args } => {
+                self.resolve_callee(name);
+                for arg in args.iter_mut().flatten() {
+                    self.resolve_expr(arg);
+                }
+            }
+            StmtKind::Assignment { target, value } => {
+                self.resolve_expr(value);
+                self.resolve_target(target);
+            }
+        }
+    }
+
+    /// Resolves a location that is assigned to, which cannot be a constant.
+    fn resolve_target(&mut self, target: &mut Expr) {
+        self.resolve_expr(target);
+        if let ExprKind::Identifier { symbol: Some(symbol), .. } = target.kind {
+            let declaration = &self.symbols[symbol];
+            if declaration.kind == SymbolKind::Constant {
+                self.errors.push(ResolveError::AssignToConstant {
+                    span: target.span,
+                    declaration: declaration.span,
+                });
+            }
+        }
+    }
+
+    fn resolve_type(&mut self, type_: &mut Type) {
+        match type_ {
+            Type::Array(ArrayType { inner_type, ranges }) => {
+                for (lower, upper) in ranges {
+                    self.resolve_expr(lower);
+                    self.resolve_expr(upper);
+                }
+                self.resolve_type(inner_type);
+            }
+            Type::Named(name) => self.resolve_type_name(name),
+            Type::Pointer(target) => self.resolve_type(target),
+            Type::Primitive(_) | Type::Enumerated(_) => {}
+        }
+    }
+
+    fn resolve_type_name(&mut self, name: &mut Expr) {
+        match handle(name).and_then(|handle| self.types.get(&handle).copied()) {
+            Some(symbol) => annotate(name, symbol),
+            None => self.errors.push(ResolveError::UndefinedType(name.span)),
+        }
+    }
+
+    /// Resolves the routine or method called through `name`.
+    fn resolve_callee(&mut self, name: &mut Expr) {
+        match &mut name.kind {
+            ExprKind::Identifier { handle, .. } if Builtin::from_handle(*handle).is_some() => {}
+            ExprKind::Identifier { handle, .. } => match self.lookup_routine(*handle) {
+                Some(symbol) => annotate(name, symbol),
+                None => self.errors.push(ResolveError::UndefinedRoutine(name.span)),
+            },
+            // Which method is called depends on the type of the object
+            ExprKind::FieldAccess { object, .. } => self.resolve_expr(object),
+            _ => self.resolve_expr(name),
+        }
+    }
+
+    fn resolve_expr(&mut self, expr: &mut Expr) {
+        match &mut expr.kind {
+            ExprKind::Binary { left, right, .. } => {
+                self.resolve_expr(left);
+                self.resolve_expr(right);
+            }
+            ExprKind::Unary { right, .. } => self.resolve_expr(right),
+            ExprKind::FunctionCall { function, args } => {
+                self.resolve_callee(function);
+                for arg in args {
+                    self.resolve_expr(arg);
+                }
+            }
+            ExprKind::ArrayIndex { array, indexes } => {
+                self.resolve_expr(array);
+                for index in indexes {
+                    self.resolve_expr(index);
+                }
+            }
+            // Fields are looked up in the type of the object, which is left to the type checker
+            ExprKind::FieldAccess { object, .. } => self.resolve_expr(object),
+            ExprKind::Dereference { pointer: inner } | ExprKind::AddressOf { target: inner } => self.resolve_expr(inner),
+            ExprKind::New { class, args } => {
+                self.resolve_type_name(class);
+                for arg in args {
+                    self.resolve_expr(arg);
+                }
+            }
+            ExprKind::Identifier { handle, symbol } => match self.lookup(*handle) {
+                Some(found) => *symbol = Some(found),
+                None => self.errors.push(ResolveError::UndeclaredVariable(expr.span)),
+            },
+            ExprKind::Super | ExprKind::Literal(_) => {}
+        }
+    }
+}
+
+/// Finds the declaration each identifier in a program refers to, recording it in the
+/// identifier's `symbol`. Globals and routines get one scope between them, each routine and
+/// method has a scope of its own, and methods can also use the attributes of their class.
+pub fn resolve(program: &mut Program) -> (Vec<Symbol>, Vec<ResolveError>) {
+    let mut resolver = Resolver::new();
+    resolver.declare_hoisted(&mut program.body);
+    resolver.resolve_block(&mut program.body);
+    for stmt in &mut program.body.contents {
+        match &mut stmt.kind {
+            StmtKind::ProcedureDecl { .. } | StmtKind::FunctionDecl { .. } => resolver.resolve_routine(stmt, None),
+            StmtKind::ClassDecl { name, members, .. } => {
+                let class = handle(name);
+                for member in members {
+                    if let Member::Method { decl, .. } = member {
+                        resolver.resolve_routine(decl, class);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    (resolver.symbols, resolver.errors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser;
+
+    fn resolve_source(source: &str) -> (Program, Vec<Symbol>, Vec<ResolveError>) {
+        let (mut program, errors) = parser::parse_program(source);
+        assert!(errors.is_empty(), "{errors:?}");
+        let (symbols, errors) = resolve(&mut program);
+        (program, symbols, errors)
+    }
+
+    fn symbol(expr: &Expr) -> Option<usize> {
+        match expr.kind {
+            ExprKind::Identifier { symbol, .. } => symbol,
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn scopes() {
+        let source = "
+            DECLARE Total : INTEGER
+            Total <- Double(2)
+            FUNCTION Double(N : INTEGER) RETURNS INTEGER
+                DECLARE Total : INTEGER
+                Total <- N * 2
+                RETURN Total
+            ENDFUNCTION
+        ";
+        let (program, symbols, errors) = resolve_source(source);
+        assert!(errors.is_empty(), "{errors:?}");
+        let StmtKind::Assignment { target, .. } = &program.body.contents[1].kind else {
+            panic!("expected assignment");
+        };
+        let global = symbol(target).unwrap();
+        assert_eq!((symbols[global].kind, symbols[global].scope), (SymbolKind::Variable, Scope::Global));
+
+        let StmtKind::FunctionDecl { name, body, .. } = &program.body.contents[2].kind else {
+            panic!("expected function");
+        };
+        let StmtKind::Assignment { target, value } = &body.contents[1].kind else {
+            panic!("expected assignment");
+        };
+        let local = symbol(target).unwrap();
+        assert_ne!(local, global);
+        assert_eq!(symbols[local].scope, Scope::Routine(symbol(name).unwrap()));
+        let ExprKind::Binary { left, .. } = &value.kind else {
+            panic!("expected multiplication");
+        };
+        assert_eq!(symbols[symbol(left).unwrap()].kind, SymbolKind::Parameter(PassingMode::ByValue));
+    }
+
+    #[test]
+    fn errors() {
+        let source = "
+            CONSTANT Limit <- 10
+            DECLARE Count : INTEGER
+            DECLARE Count : REAL
+            Limit <- Missing + 1
+            INPUT Limit
+            CALL Report(Count)
+            FOR I <- 1 TO Limit
+                OUTPUT I
+            NEXT I
+            DECLARE S : Shape
+            PROCEDURE Show(Value : INTEGER)
+                DECLARE Value : STRING
+                OUTPUT Value, Count
+            ENDPROCEDURE
+        ";
+        let (_, _, errors) = resolve_source(source);
+        assert!(matches!(
+            errors[..],
+            [
+                ResolveError::Redeclared { previous, .. },
+                ResolveError::UndeclaredVariable(missing),
+                ResolveError::AssignToConstant { .. },
+                ResolveError::AssignToConstant { .. },
+                ResolveError::UndefinedRoutine(_),
+                ResolveError::UndefinedType(_),
+                ResolveError::Redeclared { .. },
+            ] if previous.start.line == 3 && missing.start.line == 5
+        ), "{errors:?}");
+    }
+
+    #[test]
+    fn class_members() {
+        let source = "
+            CLASS Counter
+                PRIVATE Count : INTEGER
+                PUBLIC PROCEDURE NEW()
+                    Count <- 0
+                    CALL Increment()
+                ENDPROCEDURE
+                PUBLIC PROCEDURE Increment()
+                    Count <- Count + Step
+                ENDPROCEDURE
+            ENDCLASS
+            DECLARE C : Counter
+            C <- NEW Counter()
+        ";
+        let (_, symbols, errors) = resolve_source(source);
+        assert!(matches!(errors[..], [ResolveError::UndeclaredVariable(_)]), "{errors:?}");
+        assert!(symbols.iter().any(|symbol| symbol.kind == SymbolKind::Attribute && matches!(symbol.scope, Scope::Class(_))));
+    }
+}
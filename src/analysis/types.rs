@@ -0,0 +1,870 @@
+use std::collections::HashMap;
+
+use crate::ast::*;
+use crate::scanner::Span;
+
+/// The type of an expression, as far as it can be known without running the program.
+#[derive(Clone, Debug, PartialEq)]
+pub enum StaticType {
+    Primitive(PrimitiveType),
+    /// Array bounds are only known at runtime, so arrays are told apart by their dimensions
+    Array {
+        element_type: Box<StaticType>,
+        dimensions: usize,
+    },
+    /// A record type, given by the handle of its name
+    Record(usize),
+    /// An enumerated type, given by the handle of its name
+    Enum(usize),
+    /// A class, given by the handle of its name
+    Class(usize),
+    Pointer(Box<StaticType>),
+    /// The type of something that could not be worked out, such as an undeclared variable. It is
+    /// compatible with every type, so that each mistake is only reported once.
+    Unknown,
+}
+
+const BOOLEAN: StaticType = StaticType::Primitive(PrimitiveType::Boolean);
+const INTEGER: StaticType = StaticType::Primitive(PrimitiveType::Integer);
+const REAL: StaticType = StaticType::Primitive(PrimitiveType::Real);
+const STRING: StaticType = StaticType::Primitive(PrimitiveType::String);
+
+impl StaticType {
+    fn is_numeric(&self) -> bool {
+        matches!(self, StaticType::Primitive(PrimitiveType::Integer | PrimitiveType::Real))
+    }
+
+    fn is_text(&self) -> bool {
+        matches!(self, StaticType::Primitive(PrimitiveType::Char | PrimitiveType::String))
+    }
+}
+
+impl From<&Literal> for StaticType {
+    fn from(literal: &Literal) -> Self {
+        StaticType::Primitive(match literal {
+            Literal::Char(_) => PrimitiveType::Char,
+            Literal::String(_) => PrimitiveType::String,
+            Literal::Integer(_) => PrimitiveType::Integer,
+            Literal::Real(_) => PrimitiveType::Real,
+            Literal::Boolean(_) => PrimitiveType::Boolean,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub enum TypeError {
+    /// A value used where one of another type is needed, such as in an assignment, a condition
+    /// or an argument
+    Mismatch {
+        expected: StaticType,
+        found: StaticType,
+        span: Span,
+    },
+    InvalidOperands {
+        operator: BinaryOperator,
+        left: StaticType,
+        right: StaticType,
+        span: Span,
+    },
+    InvalidOperand {
+        operator: UnaryOperator,
+        operand: StaticType,
+        span: Span,
+    },
+    ArgumentCount {
+        expected: usize,
+        found: usize,
+        span: Span,
+    },
+    NotAnArray(StaticType, Span),
+    NotAPointer(StaticType, Span),
+    /// A field, attribute or method that the type does not have
+    UndefinedField(StaticType, Span),
+    /// A procedure called as part of an expression
+    NotAFunction(Span),
+    /// A pointer or object given to OUTPUT
+    NotPrintable(StaticType, Span),
+}
+
+impl TypeError {
+    pub fn span(&self) -> Span {
+        match self {
+            TypeError::Mismatch { span, .. }
+            | TypeError::InvalidOperands { span, .. }
+            | TypeError::InvalidOperand { span, .. }
+            | TypeError::ArgumentCount { span, .. }
+            | TypeError::NotAnArray(_, span)
+            | TypeError::NotAPointer(_, span)
+            | TypeError::UndefinedField(_, span)
+            | TypeError::NotAFunction(span)
+            | TypeError::NotPrintable(_, span) => *span,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Signature {
+    params: Vec<(StaticType, PassingMode)>,
+    return_type: Option<StaticType>,
+}
+
+#[derive(Debug)]
+struct Class {
+    parent: Option<usize>,
+    attributes: HashMap<usize, StaticType>,
+    methods: HashMap<usize, Signature>,
+}
+
+struct Checker<'a> {
+    /// The types that names declared with TYPE, CLASS or a record declaration stand for
+    types: HashMap<usize, StaticType>,
+    records: HashMap<usize, HashMap<usize, StaticType>>,
+    classes: HashMap<usize, Class>,
+    routines: HashMap<usize, Signature>,
+    globals: HashMap<usize, StaticType>,
+    /// The parameters and variables of the routine being checked, if any
+    locals: Option<HashMap<usize, StaticType>>,
+    /// The class the method being checked belongs to
+    class: Option<usize>,
+    return_type: Option<StaticType>,
+    /// Routines and methods, which are checked once every global has been declared
+    routine_decls: Vec<(&'a Stmt, Option<usize>)>,
+    errors: Vec<TypeError>,
+}
+
+fn handle(expr: &Expr) -> Option<usize> {
+    match expr.kind {
+        ExprKind::Identifier { handle, .. } => Some(handle),
+        _ => None,
+    }
+}
+
+/// The type of `left operator right`, or `None` if the operator cannot be used with the operands.
+fn binary(operator: BinaryOperator, left: &StaticType, right: &StaticType) -> Option<StaticType> {
+    use BinaryOperator as Op;
+
+    let equality = matches!(operator, Op::Equal | Op::NotEqual);
+    if *left == StaticType::Unknown || *right == StaticType::Unknown {
+        return Some(match operator {
+            Op::LogicAnd | Op::LogicOr => BOOLEAN,
+            Op::Div | Op::Mod => INTEGER,
+            Op::Slash | Op::Power => REAL,
+            Op::Concat => STRING,
+            Op::Plus | Op::Minus | Op::Star => StaticType::Unknown,
+            _ => BOOLEAN,
+        });
+    }
+    match operator {
+        Op::LogicAnd | Op::LogicOr => (*left == BOOLEAN && *right == BOOLEAN).then_some(BOOLEAN),
+        // Like `/`, a power is always REAL, since a negative exponent gives a fraction
+        Op::Plus | Op::Minus | Op::Star if *left == INTEGER && *right == INTEGER => Some(INTEGER),
+        Op::Plus | Op::Minus | Op::Star | Op::Power | Op::Slash => {
+            (left.is_numeric() && right.is_numeric()).then_some(REAL)
+        }
+        Op::Div | Op::Mod => (*left == INTEGER && *right == INTEGER).then_some(INTEGER),
+        Op::Concat => (left.is_text() && right.is_text()).then_some(STRING),
+        _ => {
+            let comparable = match (left, right) {
+                (StaticType::Primitive(PrimitiveType::Boolean), StaticType::Primitive(PrimitiveType::Boolean)) => {
+                    equality
+                }
+                (StaticType::Pointer(_), StaticType::Pointer(_)) | (StaticType::Class(_), StaticType::Class(_)) => {
+                    equality
+                }
+                (StaticType::Primitive(l), StaticType::Primitive(r)) if l == r => true,
+                (StaticType::Enum(l), StaticType::Enum(r)) => l == r,
+                _ => left.is_numeric() && right.is_numeric(),
+            };
+            comparable.then_some(BOOLEAN)
+        }
+    }
+}
+
+/// Whether two types are the same, treating unknown types as matching anything.
+fn same(left: &StaticType, right: &StaticType) -> bool {
+    match (left, right) {
+        (StaticType::Unknown, _) | (_, StaticType::Unknown) => true,
+        (
+            StaticType::Array {
+                element_type: left,
+                dimensions: l,
+            },
+            StaticType::Array {
+                element_type: right,
+                dimensions: r,
+            },
+        ) => l == r && same(left, right),
+        (StaticType::Pointer(left), StaticType::Pointer(right)) => same(left, right),
+        _ => left == right,
+    }
+}
+
+impl<'a> Checker<'a> {
+    fn new() -> Self {
+        Checker {
+            types: HashMap::new(),
+            records: HashMap::new(),
+            classes: HashMap::new(),
+            routines: HashMap::new(),
+            globals: HashMap::new(),
+            locals: None,
+            class: None,
+            return_type: None,
+            routine_decls: Vec::new(),
+            errors: Vec::new(),
+        }
+    }
+
+    /// Collects the types, routines and enumerated values declared in `block`, so that they can
+    /// be used before the point where they are declared.
+    fn declare_types(&mut self, block: &'a Block) {
+        for stmt in &block.contents {
+            let (name, type_) = match &stmt.kind {
+                StmtKind::RecordDecl { name, .. } => (name, StaticType::Record as fn(usize) -> StaticType),
+                StmtKind::ClassDecl { name, .. } => (name, StaticType::Class as _),
+                StmtKind::TypeDecl {
+                    name,
+                    type_: Type::Enumerated(_),
+                } => (name, StaticType::Enum as _),
+                _ => continue,
+            };
+            if let Some(handle) = handle(name) {
+                self.types.insert(handle, type_(handle));
+            }
+        }
+
+        for stmt in &block.contents {
+            match &stmt.kind {
+                StmtKind::RecordDecl { name, fields } => {
+                    let fields = fields
+                        .iter()
+                        .filter_map(|field| Some((handle(&field.name)?, self.resolve_type(&field.type_))))
+                        .collect();
+                    self.records.extend(handle(name).map(|handle| (handle, fields)));
+                }
+                StmtKind::ClassDecl { name, parent, members } => {
+                    let Some(class) = handle(name) else { continue };
+                    let mut attributes = HashMap::new();
+                    let mut methods = HashMap::new();
+                    for member in members {
+                        match member {
+                            Member::Attribute { field, .. } => {
+                                attributes.extend(handle(&field.name).map(|name| (name, self.resolve_type(&field.type_))));
+                            }
+                            Member::Method { decl, .. } => {
+                                if let Some((name, signature)) = self.signature(decl) {
+                                    methods.insert(name, signature);
+                                    self.routine_decls.push((decl, Some(class)));
+                                }
+                            }
+                        }
+                    }
+                    let parent = parent.as_ref().and_then(handle);
+                    self.classes.insert(
+                        class,
+                        Class {
+                            parent,
+                            attributes,
+                            methods,
+                        },
+                    );
+                }
+                StmtKind::TypeDecl {
+                    name,
+                    type_: Type::Enumerated(values),
+                } => {
+                    let Some(enum_type) = handle(name) else { continue };
+                    for value in values.iter().filter_map(handle) {
+                        self.globals.insert(value, StaticType::Enum(enum_type));
+                    }
+                }
+                StmtKind::TypeDecl { name, type_ } => {
+                    let type_ = self.resolve_type(type_);
+                    self.types.extend(handle(name).map(|handle| (handle, type_)));
+                }
+                StmtKind::ProcedureDecl { .. } | StmtKind::FunctionDecl { .. } => {
+                    if let Some((name, signature)) = self.signature(stmt) {
+                        self.routines.insert(name, signature);
+                        self.routine_decls.push((stmt, None));
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn signature(&self, decl: &Stmt) -> Option<(usize, Signature)> {
+        let (name, params, return_type) = match &decl.kind {
+            StmtKind::ProcedureDecl { name, params, .. } => (name, params, None),
+            StmtKind::FunctionDecl {
+                name,
+                params,
+                return_type,
+                ..
+            } => (name, params, Some(self.resolve_type(return_type))),
+            _ => return None,
+        };
+        let params = params
+            .iter()
+            .flatten()
+            .map(|param| (self.resolve_type(&param.type_), param.mode))
+            .collect();
+        Some((handle(name)?, Signature { params, return_type }))
+    }
+
+    fn resolve_type(&self, type_: &Type) -> StaticType {
+        match type_ {
+            Type::Primitive(primitive) => StaticType::Primitive(*primitive),
+            Type::Array(ArrayType { inner_type, ranges }) => StaticType::Array {
+                element_type: Box::new(self.resolve_type(inner_type)),
+                dimensions: ranges.len(),
+            },
+            Type::Named(name) => handle(name)
+                .and_then(|handle| self.types.get(&handle).cloned())
+                .unwrap_or(StaticType::Unknown),
+            Type::Pointer(target) => StaticType::Pointer(Box::new(self.resolve_type(target))),
+            Type::Enumerated(_) => StaticType::Unknown,
+        }
+    }
+
+    /// The classes `class` is made up of: itself, then each class it inherits from in turn.
+    fn lineage(&self, class: usize) -> Vec<(usize, &Class)> {
+        let mut lineage: Vec<(usize, &Class)> = Vec::new();
+        let mut current = Some(class);
+        while let Some(class) = current {
+            // A class that inherits from itself is an error the interpreter reports
+            if lineage.iter().any(|&(other, _)| other == class) {
+                break;
+            }
+            let Some(info) = self.classes.get(&class) else { break };
+            lineage.push((class, info));
+            current = info.parent;
+        }
+        lineage
+    }
+
+    fn attribute(&self, class: usize, handle: usize) -> Option<StaticType> {
+        self.lineage(class)
+            .into_iter()
+            .find_map(|(_, class)| class.attributes.get(&handle).cloned())
+    }
+
+    fn method(&self, class: usize, handle: usize) -> Option<Signature> {
+        self.lineage(class)
+            .into_iter()
+            .find_map(|(_, class)| class.methods.get(&handle).cloned())
+    }
+
+    /// Whether a value of type `found` can be stored where `expected` is needed. INTEGER values
+    /// are widened to REAL, and objects can stand in for the classes they inherit from.
+    fn assignable(&self, expected: &StaticType, found: &StaticType) -> bool {
+        match (expected, found) {
+            (StaticType::Primitive(PrimitiveType::Real), StaticType::Primitive(PrimitiveType::Integer)) => true,
+            (StaticType::Class(expected), StaticType::Class(found)) => {
+                self.lineage(*found).iter().any(|(class, _)| class == expected)
+            }
+            _ => same(expected, found),
+        }
+    }
+
+    fn declare(&mut self, handle: usize, type_: StaticType) {
+        match &mut self.locals {
+            Some(locals) => locals.insert(handle, type_),
+            None => self.globals.insert(handle, type_),
+        };
+    }
+
+    fn variable(&self, handle: usize) -> StaticType {
+        if let Some(type_) = self.locals.as_ref().and_then(|locals| locals.get(&handle)) {
+            return type_.clone();
+        }
+        // Inside a method, the attributes of the object can be used by name
+        if let Some(type_) = self.class.and_then(|class| self.attribute(class, handle)) {
+            return type_;
+        }
+        self.globals.get(&handle).cloned().unwrap_or(StaticType::Unknown)
+    }
+
+    fn mismatch(&mut self, expected: &StaticType, found: StaticType, span: Span) {
+        if !self.assignable(expected, &found) {
+            self.errors.push(TypeError::Mismatch {
+                expected: expected.clone(),
+                found,
+                span,
+            });
+        }
+    }
+
+    fn expect(&mut self, expr: &Expr, expected: &StaticType) {
+        let found = self.check_expr(expr);
+        self.mismatch(expected, found, expr.span);
+    }
+
+    fn check_routine(&mut self, decl: &Stmt, class: Option<usize>) {
+        let (params, return_type, body) = match &decl.kind {
+            StmtKind::ProcedureDecl { params, body, .. } => (params, None, body),
+            StmtKind::FunctionDecl {
+                params,
+                return_type,
+                body,
+                ..
+            } => (params, Some(return_type), body),
+            _ => return,
+        };
+        let locals = params
+            .iter()
+            .flatten()
+            .filter_map(|param| Some((handle(&param.name)?, self.resolve_type(&param.type_))))
+            .collect();
+        self.locals = Some(locals);
+        self.class = class;
+        self.return_type = return_type.map(|type_| self.resolve_type(type_));
+        self.check_block(body);
+        self.locals = None;
+        self.class = None;
+        self.return_type = None;
+    }
+
+    fn check_block(&mut self, block: &Block) {
+        for stmt in &block.contents {
+            self.check_stmt(stmt);
+        }
+    }
+
+    fn check_stmt(&mut self, stmt: &Stmt) {
+        match &stmt.kind {
+            // Declarations of types and routines are collected up front
+            StmtKind::ProcedureDecl { .. }
+            | StmtKind::FunctionDecl { .. }
+            | StmtKind::RecordDecl { .. }
+            | StmtKind::ClassDecl { .. }
+            | StmtKind::TypeDecl { .. } => {}
+            StmtKind::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.expect(condition, &BOOLEAN);
+                self.check_block(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.check_block(else_branch);
+                }
+            }
+            StmtKind::CaseOf {
+                condition,
+                cases,
+                otherwise,
+            } => {
+                self.check_expr(condition);
+                for (_, body) in cases {
+                    self.check_block(body);
+                }
+                if let Some(otherwise) = otherwise {
+                    self.check_block(otherwise);
+                }
+            }
+            StmtKind::ForLoop {
+                target,
+                start,
+                end,
+                step,
+                body,
+            } => {
+                self.expect(target, &INTEGER);
+                self.expect(start, &INTEGER);
+                self.expect(end, &INTEGER);
+                if let Some(step) = step {
+                    self.expect(step, &INTEGER);
+                }
+                self.check_block(body);
+            }
+            StmtKind::RepeatUntil { body, condition } => {
+                self.check_block(body);
+                self.expect(condition, &BOOLEAN);
+            }
+            StmtKind::While { condition, body } => {
+                self.expect(condition, &BOOLEAN);
+                self.check_block(body);
+            }
+            StmtKind::VariableDecl { name, type_ } => {
+                if let Type::Array(ArrayType { ranges, .. }) = type_ {
+                    for (lower, upper) in ranges {
+                        self.expect(lower, &INTEGER);
+                        self.expect(upper, &INTEGER);
+                    }
+                }
+                if let Some(handle) = handle(name) {
+                    let type_ = self.resolve_type(type_);
+                    self.declare(handle, type_);
+                }
+            }
+            StmtKind::ConstantDecl { name, value } => {
+                if let Some(handle) = handle(name) {
+                    self.declare(handle, value.into());
+                }
+            }
+            StmtKind::Input(targets) => {
+                for target in targets {
+                    self.check_expr(target);
+                }
+            }
+            StmtKind::Output(values) => {
+                for value in values {
+                    let type_ = self.check_expr(value);
+                    if matches!(type_, StaticType::Pointer(_) | StaticType::Class(_)) {
+                        self.errors.push(TypeError::NotPrintable(type_, value.span));
+                    }
+                }
+            }
+            StmtKind::Return(value) => {
+                let found = self.check_expr(value);
+                // A RETURN outside of a function is a problem with control flow, not types
+                if let Some(expected) = self.return_type.clone() {
+                    self.mismatch(&expected, found, value.span);
+                }
+            }
+            StmtKind::FileOpen { file, .. } | StmtKind::FileClose { file } => self.expect(file, &STRING),
+            StmtKind::FileRead { file, target: other }
+            | StmtKind::FileWrite { file, value: other }
+            | StmtKind::FileGetRecord { file, target: other }
+            | StmtKind::FilePutRecord { file, source: other } => {
+                self.expect(file, &STRING);
+                self.check_expr(other);
+            }
+            StmtKind::FileSeek { file, address } => {
+                self.expect(file, &STRING);
+                self.expect(address, &INTEGER);
+            }
+            StmtKind::Procedure { name, args } => {
+                let args = args.as_deref().unwrap_or_default();
+                match self.callee(name) {
+                    Some(signature) => self.check_args(&signature, args, stmt.span),
+                    None => args.iter().for_each(|arg| drop(self.check_expr(arg))),
+                }
+            }
+            StmtKind::Assignment { target, value } => {
+                let expected = self.check_expr(target);
+                self.expect(value, &expected);
+            }
+        }
+    }
+
+    /// Finds the signature of the routine or method that `name` calls.
+    fn callee(&mut self, name: &Expr) -> Option<Signature> {
+        match &name.kind {
+            ExprKind::Identifier { handle, .. } => {
+                if let Some(Builtin::Eof) = Builtin::from_handle(*handle) {
+                    return Some(Signature {
+                        params: vec![(STRING, PassingMode::ByValue)],
+                        return_type: Some(BOOLEAN),
+                    });
+                }
+                // Inside a method, a bare name can refer to another method of the same object
+                let own_method = self.class.and_then(|class| self.method(class, *handle));
+                own_method.or_else(|| self.routines.get(handle).cloned())
+            }
+            ExprKind::FieldAccess { object, field } => {
+                let class = match &object.kind {
+                    ExprKind::Super => self.class.and_then(|class| self.classes.get(&class)?.parent)?,
+                    _ => match self.check_expr(object) {
+                        StaticType::Class(class) => class,
+                        StaticType::Unknown => return None,
+                        other => {
+                            self.errors.push(TypeError::UndefinedField(other, field.span));
+                            return None;
+                        }
+                    },
+                };
+                let method = self.method(class, handle(field)?);
+                if method.is_none() && self.classes.contains_key(&class) {
+                    self.errors.push(TypeError::UndefinedField(StaticType::Class(class), field.span));
+                }
+                method
+            }
+            _ => None,
+        }
+    }
+
+    fn check_args(&mut self, signature: &Signature, args: &[Expr], span: Span) {
+        if args.len() != signature.params.len() {
+            self.errors.push(TypeError::ArgumentCount {
+                expected: signature.params.len(),
+                found: args.len(),
+                span,
+            });
+        }
+        for (i, arg) in args.iter().enumerate() {
+            let found = self.check_expr(arg);
+            match signature.params.get(i) {
+                Some((expected, PassingMode::ByValue)) => self.mismatch(expected, found, arg.span),
+                // A variable passed by reference must have exactly the type of the parameter
+                Some((expected, PassingMode::ByReference)) if !same(expected, &found) => {
+                    self.errors.push(TypeError::Mismatch {
+                        expected: expected.clone(),
+                        found,
+                        span: arg.span,
+                    })
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Works out the type of `expr`, reporting any type errors inside it.
+    fn check_expr(&mut self, expr: &Expr) -> StaticType {
+        match &expr.kind {
+            ExprKind::Binary {
+                left,
+                operator,
+                right,
+            } => {
+                let left = self.check_expr(left);
+                let right = self.check_expr(right);
+                binary(*operator, &left, &right).unwrap_or_else(|| {
+                    self.errors.push(TypeError::InvalidOperands {
+                        operator: *operator,
+                        left,
+                        right,
+                        span: expr.span,
+                    });
+                    StaticType::Unknown
+                })
+            }
+            ExprKind::Unary { operator, right } => {
+                let operand = self.check_expr(right);
+                let valid = match operator {
+                    UnaryOperator::LogicNot => same(&operand, &BOOLEAN),
+                    UnaryOperator::Negate => operand.is_numeric() || operand == StaticType::Unknown,
+                };
+                if !valid {
+                    self.errors.push(TypeError::InvalidOperand {
+                        operator: *operator,
+                        operand,
+                        span: expr.span,
+                    });
+                    return StaticType::Unknown;
+                }
+                match operator {
+                    UnaryOperator::LogicNot => BOOLEAN,
+                    UnaryOperator::Negate => operand,
+                }
+            }
+            ExprKind::FunctionCall { function, args } => match self.callee(function) {
+                Some(signature) => {
+                    self.check_args(&signature, args, expr.span);
+                    signature.return_type.unwrap_or_else(|| {
+                        self.errors.push(TypeError::NotAFunction(function.span));
+                        StaticType::Unknown
+                    })
+                }
+                None => {
+                    args.iter().for_each(|arg| drop(self.check_expr(arg)));
+                    StaticType::Unknown
+                }
+            },
+            ExprKind::ArrayIndex { array, indexes } => {
+                let array_type = self.check_expr(array);
+                for index in indexes {
+                    self.expect(index, &INTEGER);
+                }
+                match array_type {
+                    StaticType::Array { element_type, .. } => *element_type,
+                    StaticType::Unknown => StaticType::Unknown,
+                    other => {
+                        self.errors.push(TypeError::NotAnArray(other, array.span));
+                        StaticType::Unknown
+                    }
+                }
+            }
+            ExprKind::FieldAccess { object, field } => {
+                let object_type = self.check_expr(object);
+                let Some(field_handle) = handle(field) else {
+                    return StaticType::Unknown;
+                };
+                let field_type = match &object_type {
+                    StaticType::Record(record) => match self.records.get(record) {
+                        Some(fields) => fields.get(&field_handle).cloned(),
+                        None => Some(StaticType::Unknown),
+                    },
+                    StaticType::Class(class) => self.attribute(*class, field_handle),
+                    StaticType::Unknown => Some(StaticType::Unknown),
+                    _ => None,
+                };
+                field_type.unwrap_or_else(|| {
+                    self.errors.push(TypeError::UndefinedField(object_type, field.span));
+                    StaticType::Unknown
+                })
+            }
+            ExprKind::Dereference { pointer } => match self.check_expr(pointer) {
+                StaticType::Pointer(target) => *target,
+                StaticType::Unknown => StaticType::Unknown,
+                other => {
+                    self.errors.push(TypeError::NotAPointer(other, pointer.span));
+                    StaticType::Unknown
+                }
+            },
+            ExprKind::AddressOf { target } => StaticType::Pointer(Box::new(self.check_expr(target))),
+            ExprKind::New { class, args } => {
+                let Some(class) = handle(class).filter(|class| self.classes.contains_key(class)) else {
+                    args.iter().for_each(|arg| drop(self.check_expr(arg)));
+                    return StaticType::Unknown;
+                };
+                let constructor = self.method(class, CONSTRUCTOR).unwrap_or(Signature {
+                    params: Vec::new(),
+                    return_type: None,
+                });
+                self.check_args(&constructor, args, expr.span);
+                StaticType::Class(class)
+            }
+            // SUPER is only used to call methods, which `callee` handles
+            ExprKind::Super => StaticType::Unknown,
+            ExprKind::Identifier { handle, .. } => self.variable(*handle),
+            ExprKind::Literal(literal) => literal.into(),
+        }
+    }
+}
+
+/// Checks that the values in a program have the types they are used as, allowing INTEGER to be
+/// widened to REAL. Names that are not declared are left for `resolve` to report.
+pub fn check_types(program: &Program) -> Vec<TypeError> {
+    let mut checker = Checker::new();
+    checker.declare_types(&program.body);
+    checker.check_block(&program.body);
+    for (decl, class) in std::mem::take(&mut checker.routine_decls) {
+        checker.check_routine(decl, class);
+    }
+    checker.errors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser;
+
+    fn check(source: &str) -> Vec<TypeError> {
+        let (program, errors) = parser::parse_program(source);
+        assert!(errors.is_empty(), "{errors:?}");
+        check_types(&program)
+    }
+
+    #[test]
+    fn assignments_and_widening() {
+        let errors = check("DECLARE X : INTEGER\nX <- \"hello\"");
+        assert!(matches!(
+            errors[..],
+            [TypeError::Mismatch {
+                expected: INTEGER,
+                found: STRING,
+                span,
+            }] if span.start.offset == 25
+        ));
+
+        let source = "
+            DECLARE R : REAL
+            DECLARE I : INTEGER
+            R <- 2
+            R <- I * 2 + 1
+            I <- 7 / 2
+            I <- 7 DIV 2
+        ";
+        assert!(matches!(check(source)[..], [TypeError::Mismatch { expected: INTEGER, found: REAL, .. }]));
+
+        let errors = check("DECLARE X : INTEGER\nDECLARE R : REAL\nX <- 2 ^ -1\nX <- 2 ^ 3\nR <- 2 ^ 3");
+        let lines: Vec<_> = errors.iter().map(|error| error.span().start.line).collect();
+        assert!(errors.iter().all(|error| matches!(error, TypeError::Mismatch { expected: INTEGER, found: REAL, .. })));
+        assert_eq!(lines, [3, 4]);
+    }
+
+    #[test]
+    fn conditions_and_output() {
+        let source = "
+            DECLARE P : ^INTEGER
+            IF 1 THEN
+                OUTPUT \"a\" & 1
+            ENDIF
+            WHILE NOT 'c' DO
+                OUTPUT P, P^ + 1
+            ENDWHILE
+            REPEAT
+            UNTIL 1 < \"2\"
+        ";
+        let errors = check(source);
+        assert!(matches!(
+            errors[..],
+            [
+                TypeError::Mismatch { expected: BOOLEAN, found: INTEGER, .. },
+                TypeError::InvalidOperands { operator: BinaryOperator::Concat, .. },
+                TypeError::InvalidOperand { operator: UnaryOperator::LogicNot, .. },
+                TypeError::NotPrintable(StaticType::Pointer(_), _),
+                TypeError::InvalidOperands { operator: BinaryOperator::Less, .. },
+            ]
+        ), "{errors:?}");
+    }
+
+    #[test]
+    fn calls() {
+        let source = "
+            DECLARE Count : INTEGER
+            DECLARE Name : STRING
+            Name <- Describe(3)
+            Count <- Describe(3.5, 1)
+            CALL Increase(Count, 2)
+            CALL Increase(Name, 2.5)
+            FUNCTION Describe(N : REAL) RETURNS STRING
+                RETURN N
+            ENDFUNCTION
+            PROCEDURE Increase(BYREF Total : INTEGER, BYVAL Amount : INTEGER)
+                Total <- Total + Amount
+            ENDPROCEDURE
+        ";
+        let errors = check(source);
+        assert!(matches!(
+            errors[..],
+            [
+                TypeError::ArgumentCount { expected: 1, found: 2, .. },
+                TypeError::Mismatch { expected: INTEGER, found: STRING, .. },
+                TypeError::Mismatch { expected: INTEGER, found: STRING, .. },
+                TypeError::Mismatch { expected: INTEGER, found: REAL, .. },
+                TypeError::Mismatch { expected: STRING, found: REAL, .. },
+            ]
+        ), "{errors:?}");
+    }
+
+    #[test]
+    fn user_defined_types() {
+        let source = "
+            TYPE Point
+                DECLARE X : INTEGER
+                DECLARE Y : INTEGER
+            ENDTYPE
+            TYPE Colour = (Red, Green)
+            CLASS Shape
+                PUBLIC Name : STRING
+            ENDCLASS
+            CLASS Circle INHERITS Shape
+                PUBLIC PROCEDURE NEW(Radius : REAL)
+                    Name <- Radius
+                ENDPROCEDURE
+            ENDCLASS
+            DECLARE P : Point
+            DECLARE C : Colour
+            DECLARE S : Shape
+            P.X <- 1
+            P.Z <- 1
+            C <- Red
+            IF C = 1 THEN
+                S <- NEW Circle(2)
+            ENDIF
+            OUTPUT S.Name
+        ";
+        let errors = check(source);
+        assert!(matches!(
+            errors[..],
+            [
+                TypeError::UndefinedField(StaticType::Record(_), _),
+                TypeError::InvalidOperands { operator: BinaryOperator::Equal, .. },
+                TypeError::Mismatch { expected: STRING, found: REAL, .. },
+            ]
+        ), "{errors:?}");
+    }
+}
@@ -1,6 +1,8 @@
 use std::rc::Rc;
 
-#[derive(Debug)]
+use crate::scanner::Span;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum BinaryOperator {
     LogicAnd,
     LogicOr,
@@ -8,6 +10,10 @@ pub enum BinaryOperator {
     Minus,
     Star,
     Slash,
+    Div,
+    Mod,
+    Power,
+    Concat,
     Equal,
     NotEqual,
     LessEqual,
@@ -16,13 +22,46 @@ pub enum BinaryOperator {
     Greater,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum UnaryOperator {
     LogicNot,
+    Negate,
+}
+
+/// Functions provided by the language rather than declared in the program.
+///
+/// The parser reserves the first identifier handles for these, in the order of `Builtin::ALL`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Builtin {
+    Eof,
+}
+
+impl Builtin {
+    pub const ALL: &'static [Builtin] = &[Builtin::Eof];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Builtin::Eof => "EOF",
+        }
+    }
+
+    pub fn from_handle(handle: usize) -> Option<Builtin> {
+        Self::ALL.get(handle).copied()
+    }
 }
 
-#[derive(Debug)]
-pub enum Expr {
+/// The handle the parser gives to `NEW`, the name of every class constructor. It follows the
+/// handles reserved for builtins.
+pub const CONSTRUCTOR: usize = Builtin::ALL.len();
+
+#[derive(Clone, Debug)]
+pub struct Expr {
+    pub kind: ExprKind,
+    pub span: Span,
+}
+
+#[derive(Clone, Debug)]
+pub enum ExprKind {
     Binary {
         left: Box<Expr>,
         operator: BinaryOperator,
@@ -40,13 +79,35 @@ pub enum Expr {
         array: Box<Expr>,
         indexes: Vec<Expr>,
     },
+    FieldAccess {
+        object: Box<Expr>,
+        field: Box<Expr>,
+    },
+    /// `pointer^`, the location a pointer refers to
+    Dereference {
+        pointer: Box<Expr>,
+    },
+    /// `^target`, a pointer to a location
+    AddressOf {
+        target: Box<Expr>,
+    },
+    /// `NEW Class(args)`, creating an object
+    New {
+        class: Box<Expr>,
+        args: Vec<Expr>,
+    },
+    /// The object a method was called on, seen as an instance of its parent class
+    Super,
     Identifier {
         handle: usize,
+        /// The declaration the name refers to, as an index into the symbols found by
+        /// `analysis::resolve`. It is `None` until the program has been resolved.
+        symbol: Option<usize>,
     },
     Literal(Literal),
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub enum Literal {
     Char(char),
     String(Rc<str>),
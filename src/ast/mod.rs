@@ -1,7 +1,11 @@
 mod expr;
+mod program;
 mod stmt;
+mod tree;
 mod types;
 
 pub use expr::*;
+pub use program::*;
 pub use stmt::*;
+pub use tree::*;
 pub use types::*;
@@ -0,0 +1,50 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::ast::Block;
+
+/// The result of parsing a whole source file.
+#[derive(Clone, Debug)]
+pub struct Program {
+    pub body: Block,
+    pub symbols: SymbolTable,
+    /// The text the program was parsed from, which spans refer to
+    pub source: String,
+}
+
+/// The names of the identifiers in a program. Identifiers refer to their name by a handle, which
+/// is the same wherever the name appears.
+#[derive(Clone, Debug, Default)]
+pub struct SymbolTable {
+    names: Vec<Rc<str>>,
+    handles: HashMap<Rc<str>, usize>,
+}
+
+impl SymbolTable {
+    /// Returns the handle of `name`, giving it the next free handle if it is new.
+    pub(crate) fn intern(&mut self, name: Rc<str>) -> usize {
+        if let Some(&handle) = self.handles.get(&name) {
+            return handle;
+        }
+        let handle = self.names.len();
+        self.names.push(Rc::clone(&name));
+        self.handles.insert(name, handle);
+        handle
+    }
+
+    pub fn handle(&self, name: &str) -> Option<usize> {
+        self.handles.get(name).copied()
+    }
+
+    pub fn name(&self, handle: usize) -> Option<&str> {
+        self.names.get(handle).map(|name| &**name)
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+}
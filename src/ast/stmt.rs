@@ -1,13 +1,22 @@
 use crate::ast::{Expr, Literal, Type};
+use crate::scanner::Span;
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum FileMode {
     Read,
     Write,
+    Append,
+    Random,
 }
 
-#[derive(Debug)]
-pub enum Stmt {
+#[derive(Clone, Debug)]
+pub struct Stmt {
+    pub kind: StmtKind,
+    pub span: Span,
+}
+
+#[derive(Clone, Debug)]
+pub enum StmtKind {
     ProcedureDecl {
         name: Expr,
         params: Option<Vec<Parameter>>,
@@ -26,8 +35,8 @@ pub enum Stmt {
     },
     CaseOf {
         condition: Expr,
-        cases: Vec<(Expr, Stmt)>,
-        otherwise: Option<Box<Stmt>>,
+        cases: Vec<(CaseLabel, Block)>,
+        otherwise: Option<Block>,
     },
     ForLoop {
         target: Expr,
@@ -48,6 +57,20 @@ pub enum Stmt {
         name: Expr,
         type_: Type,
     },
+    RecordDecl {
+        name: Expr,
+        fields: Vec<Field>,
+    },
+    ClassDecl {
+        name: Expr,
+        parent: Option<Expr>,
+        members: Vec<Member>,
+    },
+    /// `TYPE name = type`, for enumerated and pointer types
+    TypeDecl {
+        name: Expr,
+        type_: Type,
+    },
     ConstantDecl {
         name: Expr,
         value: Literal,
@@ -56,19 +79,31 @@ pub enum Stmt {
     Output(Vec<Expr>),
     Return(Expr),
     FileOpen {
-        file: Literal,
+        file: Expr,
         mode: FileMode,
     },
     FileRead {
-        file: Literal,
+        file: Expr,
         target: Expr,
     },
     FileWrite {
-        file: Literal,
+        file: Expr,
         value: Expr,
     },
     FileClose {
-        file: Literal,
+        file: Expr,
+    },
+    FileSeek {
+        file: Expr,
+        address: Expr,
+    },
+    FileGetRecord {
+        file: Expr,
+        target: Expr,
+    },
+    FilePutRecord {
+        file: Expr,
+        source: Expr,
     },
     Procedure {
         name: Expr,
@@ -80,13 +115,61 @@ pub enum Stmt {
     },
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
+pub enum CaseLabel {
+    Value(Literal),
+    Range(Literal, Literal),
+}
+
+impl CaseLabel {
+    /// Returns the inclusive lower and upper bounds matched by the label.
+    pub fn bounds(&self) -> (&Literal, &Literal) {
+        match self {
+            CaseLabel::Value(value) => (value, value),
+            CaseLabel::Range(start, end) => (start, end),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
 pub struct Block {
     pub contents: Vec<Stmt>,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
+pub struct Field {
+    pub name: Expr,
+    pub type_: Type,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Visibility {
+    Public,
+    Private,
+}
+
+#[derive(Clone, Debug)]
+pub enum Member {
+    Attribute {
+        visibility: Visibility,
+        field: Field,
+    },
+    /// A method, given as a procedure or function declaration
+    Method {
+        visibility: Visibility,
+        decl: Stmt,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PassingMode {
+    ByValue,
+    ByReference,
+}
+
+#[derive(Clone, Debug)]
 pub struct Parameter {
     pub name: Expr,
     pub type_: Type,
+    pub mode: PassingMode,
 }
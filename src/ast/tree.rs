@@ -0,0 +1,313 @@
+use std::fmt::{self, Write};
+
+use crate::ast::{Block, CaseLabel, Expr, ExprKind, Member, Parameter, StmtKind, SymbolTable, Type};
+
+/// A syntax tree shown as indented text, one node to a line, with identifiers given by name.
+pub struct Tree<'a> {
+    node: Node<'a>,
+    symbols: &'a SymbolTable,
+}
+
+enum Node<'a> {
+    Block(&'a Block),
+    Expr(&'a Expr),
+}
+
+impl Block {
+    pub fn tree<'a>(&'a self, symbols: &'a SymbolTable) -> Tree<'a> {
+        Tree { node: Node::Block(self), symbols }
+    }
+}
+
+impl Expr {
+    pub fn tree<'a>(&'a self, symbols: &'a SymbolTable) -> Tree<'a> {
+        Tree { node: Node::Expr(self), symbols }
+    }
+}
+
+impl fmt::Display for Tree<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut writer = TreeWriter { out: f, symbols: self.symbols, depth: 0 };
+        match self.node {
+            Node::Block(block) => writer.block(block),
+            Node::Expr(expr) => writer.expr("", expr),
+        }
+    }
+}
+
+struct TreeWriter<'a, 'f> {
+    out: &'a mut fmt::Formatter<'f>,
+    symbols: &'a SymbolTable,
+    depth: usize,
+}
+
+impl TreeWriter<'_, '_> {
+    /// Writes a line at the current depth, starting with `label` if it is not empty.
+    fn line(&mut self, label: &str, text: fmt::Arguments) -> fmt::Result {
+        write!(self.out, "{:1$}", "", self.depth * 2)?;
+        if !label.is_empty() {
+            write!(self.out, "{label}: ")?;
+        }
+        self.out.write_fmt(text)?;
+        self.out.write_char('\n')
+    }
+
+    /// Writes a line, then whatever `children` writes indented below it.
+    fn node(
+        &mut self,
+        label: &str,
+        text: fmt::Arguments,
+        children: impl FnOnce(&mut Self) -> fmt::Result,
+    ) -> fmt::Result {
+        self.line(label, text)?;
+        self.depth += 1;
+        let result = children(self);
+        self.depth -= 1;
+        result
+    }
+
+    fn name(&self, expr: &Expr) -> String {
+        match expr.kind {
+            ExprKind::Identifier { handle, .. } => self.symbols.name(handle).unwrap_or("?").to_string(),
+            _ => "?".to_string(),
+        }
+    }
+
+    fn block(&mut self, block: &Block) -> fmt::Result {
+        block.contents.iter().try_for_each(|stmt| self.stmt(&stmt.kind))
+    }
+
+    fn labelled_block(&mut self, label: &str, block: &Block) -> fmt::Result {
+        self.node(label, format_args!("Block"), |w| w.block(block))
+    }
+
+    fn exprs(&mut self, exprs: &[Expr]) -> fmt::Result {
+        exprs.iter().try_for_each(|expr| self.expr("", expr))
+    }
+
+    fn params(&mut self, params: &Option<Vec<Parameter>>) -> fmt::Result {
+        for param in params.iter().flatten() {
+            let name = self.name(&param.name);
+            self.node("", format_args!("Parameter {:?} {name}", param.mode), |w| w.type_("", &param.type_))?;
+        }
+        Ok(())
+    }
+
+    fn stmt(&mut self, stmt: &StmtKind) -> fmt::Result {
+        match stmt {
+            StmtKind::ProcedureDecl { name, params, body } => {
+                let name = self.name(name);
+                self.node("", format_args!("ProcedureDecl {name}"), |w| {
+                    w.params(params)?;
+                    w.labelled_block("body", body)
+                })
+            }
+            StmtKind::FunctionDecl { name, params, return_type, body } => {
+                let name = self.name(name);
+                self.node("", format_args!("FunctionDecl {name}"), |w| {
+                    w.params(params)?;
+                    w.type_("returns", return_type)?;
+                    w.labelled_block("body", body)
+                })
+            }
+            StmtKind::If { condition, then_branch, else_branch } => self.node("", format_args!("If"), |w| {
+                w.expr("condition", condition)?;
+                w.labelled_block("then", then_branch)?;
+                else_branch.iter().try_for_each(|block| w.labelled_block("else", block))
+            }),
+            StmtKind::CaseOf { condition, cases, otherwise } => self.node("", format_args!("CaseOf"), |w| {
+                w.expr("condition", condition)?;
+                for (label, block) in cases {
+                    let label = match label {
+                        CaseLabel::Value(value) => format!("{value:?}"),
+                        CaseLabel::Range(start, end) => format!("{start:?} TO {end:?}"),
+                    };
+                    w.node("", format_args!("Case {label}"), |w| w.block(block))?;
+                }
+                otherwise.iter().try_for_each(|block| w.labelled_block("otherwise", block))
+            }),
+            StmtKind::ForLoop { target, start, end, step, body } => self.node("", format_args!("ForLoop"), |w| {
+                w.expr("target", target)?;
+                w.expr("start", start)?;
+                w.expr("end", end)?;
+                step.iter().try_for_each(|step| w.expr("step", step))?;
+                w.labelled_block("body", body)
+            }),
+            StmtKind::RepeatUntil { body, condition } => self.node("", format_args!("RepeatUntil"), |w| {
+                w.labelled_block("body", body)?;
+                w.expr("condition", condition)
+            }),
+            StmtKind::While { condition, body } => self.node("", format_args!("While"), |w| {
+                w.expr("condition", condition)?;
+                w.labelled_block("body", body)
+            }),
+            StmtKind::VariableDecl { name, type_ } => {
+                let name = self.name(name);
+                self.node("", format_args!("VariableDecl {name}"), |w| w.type_("", type_))
+            }
+            StmtKind::RecordDecl { name, fields } => {
+                let name = self.name(name);
+                self.node("", format_args!("RecordDecl {name}"), |w| {
+                    fields.iter().try_for_each(|field| w.type_(&w.name(&field.name), &field.type_))
+                })
+            }
+            StmtKind::ClassDecl { name, parent, members } => {
+                let name = self.name(name);
+                let parent = parent.as_ref().map(|parent| format!(" INHERITS {}", self.name(parent)));
+                self.node("", format_args!("ClassDecl {name}{}", parent.unwrap_or_default()), |w| {
+                    members.iter().try_for_each(|member| match member {
+                        Member::Attribute { visibility, field } => {
+                            let name = w.name(&field.name);
+                            w.node("", format_args!("Attribute {visibility:?} {name}"), |w| w.type_("", &field.type_))
+                        }
+                        Member::Method { visibility, decl } => {
+                            w.node("", format_args!("Method {visibility:?}"), |w| w.stmt(&decl.kind))
+                        }
+                    })
+                })
+            }
+            StmtKind::TypeDecl { name, type_ } => {
+                let name = self.name(name);
+                self.node("", format_args!("TypeDecl {name}"), |w| w.type_("", type_))
+            }
+            StmtKind::ConstantDecl { name, value } => {
+                let name = self.name(name);
+                self.line("", format_args!("ConstantDecl {name} = {value:?}"))
+            }
+            StmtKind::Input(targets) => self.node("", format_args!("Input"), |w| w.exprs(targets)),
+            StmtKind::Output(values) => self.node("", format_args!("Output"), |w| w.exprs(values)),
+            StmtKind::Return(value) => self.node("", format_args!("Return"), |w| w.expr("", value)),
+            StmtKind::FileOpen { file, mode } => {
+                self.node("", format_args!("FileOpen {mode:?}"), |w| w.expr("file", file))
+            }
+            StmtKind::FileRead { file, target } => self.node("", format_args!("FileRead"), |w| {
+                w.expr("file", file)?;
+                w.expr("target", target)
+            }),
+            StmtKind::FileWrite { file, value } => self.node("", format_args!("FileWrite"), |w| {
+                w.expr("file", file)?;
+                w.expr("value", value)
+            }),
+            StmtKind::FileClose { file } => self.node("", format_args!("FileClose"), |w| w.expr("file", file)),
+            StmtKind::FileSeek { file, address } => self.node("", format_args!("FileSeek"), |w| {
+                w.expr("file", file)?;
+                w.expr("address", address)
+            }),
+            StmtKind::FileGetRecord { file, target } => self.node("", format_args!("FileGetRecord"), |w| {
+                w.expr("file", file)?;
+                w.expr("target", target)
+            }),
+            StmtKind::FilePutRecord { file, source } => self.node("", format_args!("FilePutRecord"), |w| {
+                w.expr("file", file)?;
+                w.expr("source", source)
+            }),
+            StmtKind::Procedure { name, args } => self.node("", format_args!("Call"), |w| {
+                w.expr("procedure", name)?;
+                args.iter().try_for_each(|args| w.exprs(args))
+            }),
+            StmtKind::Assignment { target, value } => self.node("", format_args!("Assignment"), |w| {
+                w.expr("target", target)?;
+                w.expr("value", value)
+            }),
+        }
+    }
+
+    fn expr(&mut self, label: &str, expr: &Expr) -> fmt::Result {
+        match &expr.kind {
+            ExprKind::Binary { left, operator, right } => self.node(label, format_args!("Binary {operator:?}"), |w| {
+                w.expr("", left)?;
+                w.expr("", right)
+            }),
+            ExprKind::Unary { operator, right } => {
+                self.node(label, format_args!("Unary {operator:?}"), |w| w.expr("", right))
+            }
+            ExprKind::FunctionCall { function, args } => self.node(label, format_args!("FunctionCall"), |w| {
+                w.expr("function", function)?;
+                w.exprs(args)
+            }),
+            ExprKind::ArrayIndex { array, indexes } => self.node(label, format_args!("ArrayIndex"), |w| {
+                w.expr("array", array)?;
+                w.exprs(indexes)
+            }),
+            ExprKind::FieldAccess { object, field } => {
+                let field = self.name(field);
+                self.node(label, format_args!("FieldAccess {field}"), |w| w.expr("", object))
+            }
+            ExprKind::Dereference { pointer } => self.node(label, format_args!("Dereference"), |w| w.expr("", pointer)),
+            ExprKind::AddressOf { target } => self.node(label, format_args!("AddressOf"), |w| w.expr("", target)),
+            ExprKind::New { class, args } => {
+                let class = self.name(class);
+                self.node(label, format_args!("New {class}"), |w| w.exprs(args))
+            }
+            ExprKind::Super => self.line(label, format_args!("Super")),
+            ExprKind::Identifier { .. } => {
+                let name = self.name(expr);
+                self.line(label, format_args!("Identifier {name}"))
+            }
+            ExprKind::Literal(literal) => self.line(label, format_args!("{literal:?}")),
+        }
+    }
+
+    fn type_(&mut self, label: &str, type_: &Type) -> fmt::Result {
+        match type_ {
+            Type::Primitive(primitive) => self.line(label, format_args!("{primitive:?}")),
+            Type::Named(name) => {
+                let name = self.name(name);
+                self.line(label, format_args!("Named {name}"))
+            }
+            Type::Pointer(inner) => self.node(label, format_args!("Pointer"), |w| w.type_("", inner)),
+            Type::Enumerated(values) => {
+                let values: Vec<String> = values.iter().map(|value| self.name(value)).collect();
+                self.line(label, format_args!("Enumerated {}", values.join(", ")))
+            }
+            Type::Array(array) => self.node(label, format_args!("Array"), |w| {
+                for (lower, upper) in &array.ranges {
+                    w.node("", format_args!("Range"), |w| {
+                        w.expr("", lower)?;
+                        w.expr("", upper)
+                    })?;
+                }
+                w.type_("of", &array.inner_type)
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::parse_program;
+
+    #[test]
+    fn names_in_tree() {
+        let source = "
+            DECLARE Total : ARRAY[1:3] OF INTEGER
+            FOR I <- 1 TO 3
+                Total[I] <- -I * 2
+            NEXT I
+        ";
+        let (program, errors) = parse_program(source);
+        assert!(errors.is_empty(), "{errors:?}");
+        let expected = "\
+VariableDecl Total
+  Array
+    Range
+      Integer(1)
+      Integer(3)
+    of: Integer
+ForLoop
+  target: Identifier I
+  start: Integer(1)
+  end: Integer(3)
+  body: Block
+    Assignment
+      target: ArrayIndex
+        array: Identifier Total
+        Identifier I
+      value: Binary Star
+        Unary Negate
+          Identifier I
+        Integer(2)
+";
+        assert_eq!(program.body.tree(&program.symbols).to_string(), expected);
+    }
+}
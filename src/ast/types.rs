@@ -1,12 +1,17 @@
 use crate::ast::Expr;
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub enum Type {
     Array(ArrayType),
     Primitive(PrimitiveType),
+    /// A user-defined type, referred to by its identifier
+    Named(Expr),
+    Pointer(Box<Type>),
+    /// The values of an enumerated type, in order. Only allowed in a TYPE declaration.
+    Enumerated(Vec<Expr>),
 }
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum PrimitiveType {
     Char,
     String,
@@ -15,8 +20,8 @@ pub enum PrimitiveType {
     Boolean,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct ArrayType {
-    pub inner_type: PrimitiveType,
+    pub inner_type: Box<Type>,
     pub ranges: Vec<(Expr, Expr)>,
 }
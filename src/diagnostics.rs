@@ -0,0 +1,710 @@
+//! Descriptions of the problems found in a program.
+//!
+//! Each problem has a stable code, so that it can be looked up in documentation or matched by
+//! tools. Codes starting with `E0` come from the scanner, `E1` from the parser, `E2` from the
+//! type checker, `E3` from name resolution, `E4` from control-flow checks, `E5` from running the
+//! program, and `W` are warnings. Diagnostics can be rendered as text for people to read, or as
+//! JSON for tools.
+
+use std::error::Error;
+use std::fmt;
+
+use crate::analysis::{AssignmentWarning, FlowError, FlowWarning, ResolveError, StaticType, TypeError};
+use crate::ast::{BinaryOperator, SymbolTable, UnaryOperator};
+use crate::interpreter::{RuntimeError, ValueType, MAX_CALL_DEPTH};
+use crate::parser::ParserError;
+use crate::scanner::{Location, ScannerError, ScannerWarning, Span, TokenType};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+/// A problem in the source code of a program, in a form shared by every stage that finds them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    pub code: &'static str,
+    pub severity: Severity,
+    pub message: String,
+    pub span: Span,
+    pub fix: Option<Fix>,
+}
+
+/// A suggested change that would correct a problem: replacing the text in `span`, which may be
+/// empty, with `replacement`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Fix {
+    pub message: String,
+    pub span: Span,
+    pub replacement: String,
+}
+
+impl Diagnostic {
+    fn error(code: &'static str, error: &impl fmt::Display, span: Span) -> Self {
+        Diagnostic {
+            code,
+            severity: Severity::Error,
+            message: error.to_string(),
+            span,
+            fix: None,
+        }
+    }
+
+    /// The diagnostic for an error that stopped a program while it ran, where `span` is the
+    /// statement or expression that failed.
+    pub fn runtime(error: &RuntimeError, symbols: &SymbolTable, span: Span) -> Self {
+        let code = match error {
+            RuntimeError::UndeclaredVariable(_) => "E501",
+            RuntimeError::UnassignedVariable(_) => "E502",
+            RuntimeError::AssignToConstant(_) => "E503",
+            RuntimeError::ReferenceMismatch { .. } => "E504",
+            RuntimeError::UndefinedRoutine(_) => "E505",
+            RuntimeError::UndefinedType(_) => "E506",
+            RuntimeError::UndefinedField(_) => "E507",
+            RuntimeError::UndefinedClass(_) => "E508",
+            RuntimeError::PrivateMember(_) => "E509",
+            RuntimeError::NoSuperclass => "E510",
+            RuntimeError::NotAProcedure(_) => "E511",
+            RuntimeError::NotAFunction(_) => "E512",
+            RuntimeError::MissingReturn(_) => "E513",
+            RuntimeError::UnexpectedReturn => "E514",
+            RuntimeError::StackOverflow => "E515",
+            RuntimeError::ArgumentCount { .. } => "E516",
+            RuntimeError::TypeMismatch { .. } => "E517",
+            RuntimeError::InvalidOperands(..) => "E518",
+            RuntimeError::InvalidOperand(..) => "E519",
+            RuntimeError::InvalidTarget => "E520",
+            RuntimeError::InvalidBounds(..) => "E521",
+            RuntimeError::IndexOutOfBounds { .. } => "E522",
+            RuntimeError::DimensionMismatch { .. } => "E523",
+            RuntimeError::NotAnArray(_) => "E524",
+            RuntimeError::NotARecord(_) => "E525",
+            RuntimeError::NotAPointer(_) => "E526",
+            RuntimeError::NotAnObject(_) => "E527",
+            RuntimeError::InvalidStep => "E528",
+            RuntimeError::DivisionByZero => "E529",
+            RuntimeError::Overflow => "E530",
+            RuntimeError::InvalidInput(_) => "E531",
+            RuntimeError::EndOfInput => "E532",
+            RuntimeError::InvalidFileName(_) => "E533",
+            RuntimeError::FileAlreadyOpen(_) => "E534",
+            RuntimeError::FileNotOpen(_) => "E535",
+            RuntimeError::WrongFileMode(_) => "E536",
+            RuntimeError::EndOfFile(_) => "E537",
+            RuntimeError::UnknownRecordSize(_) => "E538",
+            RuntimeError::InvalidRecordAddress(_) => "E539",
+            RuntimeError::InvalidRecord => "E540",
+            RuntimeError::NotStorable(_) => "E541",
+            RuntimeError::StringTooLong(_) => "E542",
+            RuntimeError::Io(_) => "E543",
+        };
+        Diagnostic::error(code, &error.display(symbols), span)
+    }
+
+    /// Writes the diagnostic as a JSON object.
+    pub fn to_json(&self) -> String {
+        let fix = match &self.fix {
+            Some(fix) => format!(
+                "{{\"message\":{},\"span\":{},\"replacement\":{}}}",
+                json_string(&fix.message),
+                span_json(fix.span),
+                json_string(&fix.replacement)
+            ),
+            None => "null".to_string(),
+        };
+        format!(
+            "{{\"code\":\"{}\",\"severity\":\"{}\",\"message\":{},\"span\":{},\"fix\":{fix}}}",
+            self.code,
+            self.severity,
+            json_string(&self.message),
+            span_json(self.span)
+        )
+    }
+
+    fn with_fix(mut self, message: String, span: Span, replacement: String) -> Self {
+        self.fix = Some(Fix {
+            message,
+            span,
+            replacement,
+        });
+        self
+    }
+}
+
+impl From<&ScannerError> for Diagnostic {
+    fn from(error: &ScannerError) -> Self {
+        let code = match error {
+            ScannerError::InvalidCharLiteral(_) => "E001",
+            ScannerError::UnterminatedString(_) => "E002",
+            ScannerError::InvalidRealLiteral(_) => "E003",
+            ScannerError::UnexpectedCharacter(..) => "E004",
+            ScannerError::NumberTooLarge(_) => "E005",
+        };
+        Diagnostic::error(code, error, error.span())
+    }
+}
+
+impl From<&ScannerWarning> for Diagnostic {
+    fn from(warning: &ScannerWarning) -> Self {
+        let (code, expected) = match warning {
+            ScannerWarning::KeywordCase { expected, .. } => ("W001", &**expected),
+            ScannerWarning::NonAsciiSymbol { expected, .. } => ("W002", *expected),
+        };
+        let diagnostic = Diagnostic {
+            severity: Severity::Warning,
+            ..Diagnostic::error(code, warning, warning.span())
+        };
+        diagnostic.with_fix(format!("write {expected}"), warning.span(), expected.to_string())
+    }
+}
+
+impl From<&ParserError> for Diagnostic {
+    fn from(error: &ParserError) -> Self {
+        let code = match error {
+            ParserError::Scanner(error) => return error.into(),
+            ParserError::UnexpectedToken(_) => "E101",
+            ParserError::UnexpectedEOF(_) => "E102",
+            ParserError::MissingCall(_) => "E103",
+            ParserError::DuplicateCaseLabel(_) => "E104",
+            ParserError::OverlappingCaseLabel(_) => "E105",
+            ParserError::UnclosedBlock { .. } => "E106",
+        };
+        let diagnostic = Diagnostic::error(code, error, error.span());
+        match error {
+            ParserError::MissingCall(token) => {
+                let span = Span {
+                    start: token.span.start,
+                    end: token.span.start,
+                };
+                diagnostic.with_fix("add CALL".to_string(), span, "CALL ".to_string())
+            }
+            ParserError::UnclosedBlock { expected, span, .. } => {
+                let keyword = keyword(expected);
+                let span = Span {
+                    start: span.start,
+                    end: span.start,
+                };
+                diagnostic.with_fix(format!("add {keyword}"), span, format!("{keyword}\n"))
+            }
+            _ => diagnostic,
+        }
+    }
+}
+
+impl From<&TypeError> for Diagnostic {
+    fn from(error: &TypeError) -> Self {
+        let code = match error {
+            TypeError::Mismatch { .. } => "E201",
+            TypeError::InvalidOperands { .. } => "E202",
+            TypeError::InvalidOperand { .. } => "E203",
+            TypeError::ArgumentCount { .. } => "E204",
+            TypeError::NotAnArray(..) => "E205",
+            TypeError::NotAPointer(..) => "E206",
+            TypeError::UndefinedField(..) => "E207",
+            TypeError::NotAFunction(_) => "E208",
+            TypeError::NotPrintable(..) => "E209",
+        };
+        Diagnostic::error(code, error, error.span())
+    }
+}
+
+impl From<&ResolveError> for Diagnostic {
+    fn from(error: &ResolveError) -> Self {
+        let code = match error {
+            ResolveError::UndeclaredVariable(_) => "E301",
+            ResolveError::UndefinedRoutine(_) => "E302",
+            ResolveError::UndefinedType(_) => "E303",
+            ResolveError::Redeclared { .. } => "E304",
+            ResolveError::AssignToConstant { .. } => "E305",
+        };
+        Diagnostic::error(code, error, error.span())
+    }
+}
+
+impl From<&FlowError> for Diagnostic {
+    fn from(error: &FlowError) -> Self {
+        let code = match error {
+            FlowError::MissingReturn(_) => "E401",
+            FlowError::ReturnOutsideFunction(_) => "E402",
+        };
+        Diagnostic::error(code, error, error.span())
+    }
+}
+
+impl From<&FlowWarning> for Diagnostic {
+    fn from(warning: &FlowWarning) -> Self {
+        let code = match warning {
+            FlowWarning::Unreachable(_) => "W003",
+            FlowWarning::UnchangingCondition(_) => "W004",
+        };
+        Diagnostic {
+            severity: Severity::Warning,
+            ..Diagnostic::error(code, warning, warning.span())
+        }
+    }
+}
+
+impl From<&AssignmentWarning> for Diagnostic {
+    fn from(warning: &AssignmentWarning) -> Self {
+        let code = match warning {
+            AssignmentWarning::Unassigned { .. } => "W005",
+        };
+        Diagnostic {
+            severity: Severity::Warning,
+            ..Diagnostic::error(code, warning, warning.span())
+        }
+    }
+}
+
+impl fmt::Display for ScannerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ScannerError::InvalidCharLiteral(_) => write!(f, "a CHAR literal must contain exactly one character"),
+            ScannerError::UnterminatedString(_) => write!(f, "this string has no closing quote"),
+            ScannerError::InvalidRealLiteral(_) => {
+                write!(f, "a REAL literal needs digits on both sides of the decimal point")
+            }
+            ScannerError::UnexpectedCharacter(c, _) => write!(f, "unexpected character '{c}'"),
+            ScannerError::NumberTooLarge(_) => write!(f, "this number is too large to be stored"),
+        }
+    }
+}
+
+impl Error for ScannerError {}
+
+impl fmt::Display for ScannerWarning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ScannerWarning::KeywordCase { expected, .. } => {
+                write!(f, "keywords are written in capitals, as {expected}")
+            }
+            ScannerWarning::NonAsciiSymbol { found, expected, .. } => {
+                write!(f, "'{found}' can be written as {expected}")
+            }
+        }
+    }
+}
+
+impl fmt::Display for ParserError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParserError::UnexpectedToken(token) => write!(f, "unexpected '{}'", token.lexeme),
+            ParserError::UnexpectedEOF(_) => write!(f, "unexpected end of file"),
+            ParserError::MissingCall(token) => {
+                write!(f, "procedures must be called with CALL, as in CALL {}(…)", token.lexeme)
+            }
+            ParserError::DuplicateCaseLabel(token) => {
+                write!(f, "the CASE label {} is used more than once", token.lexeme)
+            }
+            ParserError::OverlappingCaseLabel(_) => write!(f, "this CASE label overlaps an earlier one"),
+            ParserError::UnclosedBlock { opener, expected, .. } => write!(
+                f,
+                "expected {} to close {} on line {}",
+                keyword(expected),
+                keyword(&opener.type_),
+                opener.span.start.line
+            ),
+            ParserError::Scanner(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+impl Error for ParserError {}
+
+impl fmt::Display for StaticType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StaticType::Primitive(primitive) => write!(f, "{}", format!("{primitive:?}").to_ascii_uppercase()),
+            StaticType::Array { element_type, .. } => write!(f, "ARRAY OF {element_type}"),
+            StaticType::Record(_) => write!(f, "a record"),
+            StaticType::Enum(_) => write!(f, "an enumerated value"),
+            StaticType::Class(_) => write!(f, "an object"),
+            StaticType::Pointer(target) => write!(f, "a pointer to {target}"),
+            StaticType::Unknown => write!(f, "an unknown type"),
+        }
+    }
+}
+
+impl fmt::Display for TypeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TypeError::Mismatch { expected, found, .. } => write!(f, "expected {expected}, found {found}"),
+            TypeError::InvalidOperands {
+                operator, left, right, ..
+            } => write!(f, "{} cannot be used with {left} and {right}", binary_operator(*operator)),
+            TypeError::InvalidOperand { operator, operand, .. } => {
+                let operator = unary_operator(*operator);
+                write!(f, "{operator} cannot be used with {operand}")
+            }
+            TypeError::ArgumentCount { expected, found, .. } => {
+                let s = if *expected == 1 { "" } else { "s" };
+                write!(f, "expected {expected} argument{s}, found {found}")
+            }
+            TypeError::NotAnArray(found, _) => write!(f, "{found} cannot be indexed"),
+            TypeError::NotAPointer(found, _) => write!(f, "{found} cannot be dereferenced"),
+            TypeError::UndefinedField(found, _) => write!(f, "{found} has no member of this name"),
+            TypeError::NotAFunction(_) => write!(f, "a procedure does not return a value"),
+            TypeError::NotPrintable(found, _) => write!(f, "{found} cannot be output"),
+        }
+    }
+}
+
+impl Error for TypeError {}
+
+impl fmt::Display for ResolveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ResolveError::UndeclaredVariable(_) => write!(f, "this variable has not been declared"),
+            ResolveError::UndefinedRoutine(_) => write!(f, "no procedure or function has this name"),
+            ResolveError::UndefinedType(_) => write!(f, "no type has this name"),
+            ResolveError::Redeclared { previous, .. } => {
+                write!(f, "this name was already declared on line {}", previous.start.line)
+            }
+            ResolveError::AssignToConstant { declaration, .. } => {
+                write!(f, "cannot assign to a constant, declared on line {}", declaration.start.line)
+            }
+        }
+    }
+}
+
+impl Error for ResolveError {}
+
+impl fmt::Display for FlowError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FlowError::MissingReturn(_) => write!(f, "this function does not return a value on every path"),
+            FlowError::ReturnOutsideFunction(_) => write!(f, "RETURN can only be used inside a function"),
+        }
+    }
+}
+
+impl Error for FlowError {}
+
+impl fmt::Display for FlowWarning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FlowWarning::Unreachable(_) => write!(f, "this statement can never run, as it follows a RETURN"),
+            FlowWarning::UnchangingCondition(_) => {
+                write!(f, "this condition never changes, as the loop assigns none of the variables it reads")
+            }
+        }
+    }
+}
+
+impl Error for FlowWarning {}
+
+impl fmt::Display for AssignmentWarning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AssignmentWarning::Unassigned { declaration, .. } => write!(
+                f,
+                "this variable may not have been given a value yet, since its declaration on line {}",
+                declaration.start.line
+            ),
+        }
+    }
+}
+
+impl Error for AssignmentWarning {}
+
+/// A runtime error ready to be shown, with the names of the identifiers it refers to looked up
+/// in the symbol table of the program that was run.
+pub struct RuntimeErrorDisplay<'a> {
+    error: &'a RuntimeError,
+    symbols: &'a SymbolTable,
+}
+
+impl RuntimeError {
+    pub fn display<'a>(&'a self, symbols: &'a SymbolTable) -> RuntimeErrorDisplay<'a> {
+        RuntimeErrorDisplay { error: self, symbols }
+    }
+}
+
+impl RuntimeErrorDisplay<'_> {
+    fn name(&self, handle: usize) -> &str {
+        self.symbols.name(handle).unwrap_or("?")
+    }
+
+    fn type_name(&self, type_: &ValueType) -> String {
+        match type_ {
+            ValueType::Primitive(primitive) => format!("{primitive:?}").to_ascii_uppercase(),
+            ValueType::Array { element_type, .. } => format!("ARRAY OF {}", self.type_name(element_type)),
+            ValueType::Record(record_type) => self.name(record_type.name).to_string(),
+            ValueType::Enum(enum_type) => self.name(enum_type.name).to_string(),
+            ValueType::Class(class_type) => self.name(class_type.name).to_string(),
+            ValueType::Pointer(target) => format!("a pointer to {}", self.type_name(target)),
+            ValueType::Named(handle) => self.name(*handle).to_string(),
+        }
+    }
+}
+
+impl fmt::Display for RuntimeErrorDisplay<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.error {
+            RuntimeError::UndeclaredVariable(handle) => write!(f, "{} has not been declared", self.name(*handle)),
+            RuntimeError::UnassignedVariable(handle) => write!(f, "{} has not been given a value", self.name(*handle)),
+            RuntimeError::AssignToConstant(handle) => write!(f, "cannot assign to the constant {}", self.name(*handle)),
+            RuntimeError::ReferenceMismatch { expected, found } => write!(
+                f,
+                "a BYREF argument must be a variable of type {}, not {}",
+                self.type_name(expected),
+                self.type_name(found)
+            ),
+            RuntimeError::UndefinedRoutine(handle) => {
+                write!(f, "no procedure or function is called {}", self.name(*handle))
+            }
+            RuntimeError::UndefinedType(handle) => write!(f, "no type is called {}", self.name(*handle)),
+            RuntimeError::UndefinedField(handle) => {
+                write!(f, "there is no field or member called {}", self.name(*handle))
+            }
+            RuntimeError::UndefinedClass(handle) => write!(f, "no class is called {}", self.name(*handle)),
+            RuntimeError::PrivateMember(handle) => write!(f, "{} is private to its class", self.name(*handle)),
+            RuntimeError::NoSuperclass => write!(f, "SUPER can only be used in a class that inherits from another"),
+            RuntimeError::NotAProcedure(handle) => {
+                write!(f, "{} is not a procedure, so cannot be used with CALL", self.name(*handle))
+            }
+            RuntimeError::NotAFunction(handle) => {
+                write!(f, "{} is not a function, so has no value to use", self.name(*handle))
+            }
+            RuntimeError::MissingReturn(handle) => {
+                write!(f, "the function {} ended without returning a value", self.name(*handle))
+            }
+            RuntimeError::UnexpectedReturn => write!(f, "RETURN can only be used inside a function"),
+            RuntimeError::StackOverflow => {
+                write!(f, "calls were nested more than {MAX_CALL_DEPTH} deep; check that recursion stops")
+            }
+            RuntimeError::ArgumentCount { expected, found } => {
+                write!(f, "expected {expected} arguments, found {found}")
+            }
+            RuntimeError::TypeMismatch { expected, found } => {
+                write!(f, "expected {}, found {found}", self.type_name(expected))
+            }
+            RuntimeError::InvalidOperands(operator, left, right) => {
+                write!(f, "{} cannot be used with {left} and {right}", binary_operator(*operator))
+            }
+            RuntimeError::InvalidOperand(operator, operand) => {
+                write!(f, "{} cannot be used with {operand}", unary_operator(*operator))
+            }
+            RuntimeError::InvalidTarget => {
+                write!(f, "only a variable, element, field or pointed-to value can be assigned")
+            }
+            RuntimeError::InvalidBounds(lower, upper) => {
+                write!(f, "the array bounds {lower}:{upper} are invalid, as the lower is above the upper")
+            }
+            RuntimeError::IndexOutOfBounds { index, bounds: (lower, upper) } => {
+                write!(f, "index {index} is outside the array bounds {lower}:{upper}")
+            }
+            RuntimeError::DimensionMismatch { expected, found } => {
+                write!(f, "expected {expected} indexes, found {found}")
+            }
+            RuntimeError::NotAnArray(value) => write!(f, "{value} is not an array"),
+            RuntimeError::NotARecord(value) => write!(f, "{value} is not a record"),
+            RuntimeError::NotAPointer(value) => write!(f, "{value} is not a pointer"),
+            RuntimeError::NotAnObject(value) => write!(f, "{value} is not an object"),
+            RuntimeError::InvalidStep => write!(f, "the STEP of a FOR loop cannot be 0"),
+            RuntimeError::DivisionByZero => write!(f, "division by zero"),
+            RuntimeError::Overflow => write!(f, "the result is too large for an INTEGER"),
+            RuntimeError::InvalidInput(input) => write!(f, "\"{input}\" is not a valid value for this variable"),
+            RuntimeError::EndOfInput => write!(f, "there is no more input to read"),
+            RuntimeError::InvalidFileName(name) => write!(f, "\"{name}\" is not a valid file name"),
+            RuntimeError::FileAlreadyOpen(name) => write!(f, "the file {name} is already open"),
+            RuntimeError::FileNotOpen(name) => write!(f, "the file {name} is not open"),
+            RuntimeError::WrongFileMode(name) => write!(f, "the file {name} was not opened for this"),
+            RuntimeError::EndOfFile(name) => write!(f, "the end of the file {name} has been reached"),
+            RuntimeError::UnknownRecordSize(name) => {
+                write!(f, "the size of the records in {name} is not known until one has been read or written")
+            }
+            RuntimeError::InvalidRecordAddress(address) => write!(f, "{address} is not a valid record address"),
+            RuntimeError::InvalidRecord => write!(f, "the file does not hold a record of this type"),
+            RuntimeError::NotStorable(type_) => {
+                write!(f, "values of type {} cannot be stored in a file", self.type_name(type_))
+            }
+            RuntimeError::StringTooLong(string) => {
+                write!(f, "the string \"{string}\" is too long to store in a record")
+            }
+            RuntimeError::Io(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+/// The spelling of a keyword, which is the name of its token type in capitals.
+fn keyword(type_: &TokenType) -> String {
+    format!("{type_:?}").to_ascii_uppercase()
+}
+
+fn unary_operator(operator: UnaryOperator) -> &'static str {
+    match operator {
+        UnaryOperator::LogicNot => "NOT",
+        UnaryOperator::Negate => "-",
+    }
+}
+
+fn binary_operator(operator: BinaryOperator) -> &'static str {
+    match operator {
+        BinaryOperator::LogicAnd => "AND",
+        BinaryOperator::LogicOr => "OR",
+        BinaryOperator::Plus => "+",
+        BinaryOperator::Minus => "-",
+        BinaryOperator::Star => "*",
+        BinaryOperator::Slash => "/",
+        BinaryOperator::Div => "DIV",
+        BinaryOperator::Mod => "MOD",
+        BinaryOperator::Power => "^",
+        BinaryOperator::Concat => "&",
+        BinaryOperator::Equal => "=",
+        BinaryOperator::NotEqual => "<>",
+        BinaryOperator::LessEqual => "<=",
+        BinaryOperator::GreaterEqual => ">=",
+        BinaryOperator::Less => "<",
+        BinaryOperator::Greater => ">",
+    }
+}
+
+/// Describes a diagnostic along with the line of `source` it refers to, underlining its span.
+/// `path` names the file the source was read from.
+pub fn render(diagnostic: &Diagnostic, source: &str, path: &str) -> String {
+    let Span { start, end } = diagnostic.span;
+    let mut report = format!(
+        "{}[{}]: {}\n --> {path}:{}:{}\n",
+        diagnostic.severity, diagnostic.code, diagnostic.message, start.line, start.column
+    );
+    let Some(text) = source.lines().nth(start.line as usize - 1) else {
+        return report;
+    };
+    let number = start.line.to_string();
+    let gutter = " ".repeat(number.len());
+    // Tabs are kept so that the carets line up with the text above them
+    let indent: String = text
+        .chars()
+        .take(start.column as usize - 1)
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect();
+    let width = if end.line == start.line {
+        end.column.saturating_sub(start.column) as usize
+    } else {
+        text.chars().count() + 1 - start.column as usize
+    };
+    report += &format!("{gutter} |\n{number} | {text}\n{gutter} | {indent}{}\n", "^".repeat(width.max(1)));
+    if let Some(fix) = &diagnostic.fix {
+        report += &format!("{gutter} = help: {}\n", fix.message);
+    }
+    report
+}
+
+/// Writes diagnostics as a JSON document, with the file they were found in.
+pub fn to_json(diagnostics: &[Diagnostic], path: &str) -> String {
+    let entries: Vec<String> = diagnostics.iter().map(Diagnostic::to_json).collect();
+    format!("{{\"file\":{},\"diagnostics\":[{}]}}", json_string(path), entries.join(","))
+}
+
+fn span_json(span: Span) -> String {
+    let location = |location: Location| {
+        format!(
+            "{{\"offset\":{},\"line\":{},\"column\":{}}}",
+            location.offset, location.line, location.column
+        )
+    };
+    format!("{{\"start\":{},\"end\":{}}}", location(span.start), location(span.end))
+}
+
+fn json_string(s: &str) -> String {
+    let mut json = String::from('"');
+    for c in s.chars() {
+        match c {
+            '"' => json += "\\\"",
+            '\\' => json += "\\\\",
+            '\n' => json += "\\n",
+            '\t' => json += "\\t",
+            c if c.is_control() => json += &format!("\\u{:04x}", c as u32),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::Interpreter;
+    use crate::parser;
+
+    fn parse(source: &str) -> Vec<Diagnostic> {
+        let (_, errors) = parser::parse_program(source);
+        errors.iter().map(Diagnostic::from).collect()
+    }
+
+    #[test]
+    fn render_errors() {
+        let source = "DECLARE X : INTEGER\nIF X > 1 THEN\n\tOUTPUT X\nENDWHILE\n";
+        assert_eq!(
+            render(&parse(source)[0], source, "test.txt"),
+            "error[E106]: expected ENDIF to close IF on line 2
+ --> test.txt:4:1
+  |
+4 | ENDWHILE
+  | ^^^^^^^^
+  = help: add ENDIF
+"
+        );
+
+        let source = "X <- \"héllo\n";
+        let diagnostics = parse(source);
+        assert_eq!(diagnostics[0].message, "this string has no closing quote");
+        assert!(render(&diagnostics[0], source, "test.txt").ends_with("1 | X <- \"héllo\n  |      ^^^^^^\n"));
+
+        assert!(render(&parse("OUTPUT")[0], "OUTPUT", "test.txt").ends_with("1 | OUTPUT\n  |       ^\n"));
+    }
+
+    #[test]
+    fn json() {
+        let diagnostics = parse("Greet(\"Jo\")");
+        assert_eq!(
+            to_json(&diagnostics, "C:\\work\\a.txt"),
+            concat!(
+                r#"{"file":"C:\\work\\a.txt","diagnostics":[{"code":"E103","severity":"error","#,
+                r#""message":"procedures must be called with CALL, as in CALL Greet(…)","#,
+                r#""span":{"start":{"offset":0,"line":1,"column":1},"end":{"offset":5,"line":1,"column":6}},"#,
+                r#""fix":{"message":"add CALL","#,
+                r#""span":{"start":{"offset":0,"line":1,"column":1},"end":{"offset":0,"line":1,"column":1}},"#,
+                r#""replacement":"CALL "}}]}"#
+            )
+        );
+        assert_eq!(to_json(&[], "a.txt"), r#"{"file":"a.txt","diagnostics":[]}"#);
+    }
+
+    #[test]
+    fn runtime_errors() {
+        let diagnostic = |source: &str| {
+            let (program, errors) = parser::parse_program(source);
+            assert!(errors.is_empty(), "{errors:?}");
+            let mut interpreter = Interpreter::new(&b""[..], Vec::new());
+            let error = interpreter.run(&program.body).unwrap_err();
+            Diagnostic::runtime(&error, &program.symbols, interpreter.error_span().unwrap())
+        };
+        let message = |source: &str| diagnostic(source).message;
+        assert_eq!(message("DECLARE Total : INTEGER\nOUTPUT Total"), "Total has not been given a value");
+        assert_eq!(
+            message("DECLARE A : ARRAY[1:2] OF INTEGER\nA[5] <- 1"),
+            "index 5 is outside the array bounds 1:2"
+        );
+        assert_eq!(message("DECLARE X : INTEGER\nX <- 2 ^ -1"), "expected INTEGER, found 0.5");
+
+        // The span is the innermost statement that failed, here inside the procedure
+        let source = "PROCEDURE Halve(N : INTEGER)\n    OUTPUT N DIV 0\nENDPROCEDURE\nCALL Halve(4)\n";
+        assert_eq!(
+            render(&diagnostic(source), source, "a.txt"),
+            "error[E529]: division by zero\n --> a.txt:2:5\n  |\n2 |     OUTPUT N DIV 0\n  |     ^^^^^^^^^^^^^^\n"
+        );
+    }
+}
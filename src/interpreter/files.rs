@@ -0,0 +1,168 @@
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Component, Path, PathBuf};
+
+use crate::ast::FileMode;
+use crate::interpreter::serialize::{decode, encode, encoded_size};
+use crate::interpreter::{RuntimeError, Value, ValueType};
+
+enum OpenFile {
+    Reader(BufReader<File>),
+    Writer(BufWriter<File>),
+    Random(RandomFile),
+}
+
+/// A file of fixed-size records, addressed by record number.
+struct RandomFile {
+    file: File,
+    record: u64,
+    /// The size of each record, which is known once one has been read or written
+    record_size: Option<u64>,
+}
+
+impl RandomFile {
+    /// Positions the file at the current record, which is then advanced past.
+    fn next_record(&mut self, type_: &ValueType) -> Result<usize, RuntimeError> {
+        let size = encoded_size(type_);
+        self.record_size = Some(size as u64);
+        self.file.seek(SeekFrom::Start(self.record * size as u64))?;
+        self.record += 1;
+        Ok(size)
+    }
+}
+
+/// The files opened by a program, resolved relative to a root directory. Text files are read or
+/// written a line at a time, and random files a record at a time.
+pub(super) struct Files {
+    root: PathBuf,
+    open: HashMap<String, OpenFile>,
+}
+
+impl Files {
+    pub fn new(root: PathBuf) -> Self {
+        Files {
+            root,
+            open: HashMap::new(),
+        }
+    }
+
+    /// Only plain relative paths are accepted so programs cannot escape the root directory.
+    fn path(&self, name: &str) -> Result<PathBuf, RuntimeError> {
+        let path = Path::new(name);
+        if name.is_empty() || !path.components().all(|c| matches!(c, Component::Normal(_))) {
+            return Err(RuntimeError::InvalidFileName(name.to_string()));
+        }
+        Ok(self.root.join(path))
+    }
+
+    pub fn open(&mut self, name: &str, mode: FileMode) -> Result<(), RuntimeError> {
+        if self.open.contains_key(name) {
+            return Err(RuntimeError::FileAlreadyOpen(name.to_string()));
+        }
+        let path = self.path(name)?;
+        let file = match mode {
+            FileMode::Read => OpenFile::Reader(BufReader::new(File::open(path)?)),
+            FileMode::Write => OpenFile::Writer(BufWriter::new(File::create(path)?)),
+            FileMode::Append => {
+                let file = OpenOptions::new().append(true).create(true).open(path)?;
+                OpenFile::Writer(BufWriter::new(file))
+            }
+            FileMode::Random => {
+                let file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(path)?;
+                OpenFile::Random(RandomFile {
+                    file,
+                    record: 0,
+                    record_size: None,
+                })
+            }
+        };
+        self.open.insert(name.to_string(), file);
+        Ok(())
+    }
+
+    fn reader(&mut self, name: &str) -> Result<&mut BufReader<File>, RuntimeError> {
+        match self.open.get_mut(name) {
+            Some(OpenFile::Reader(reader)) => Ok(reader),
+            Some(_) => Err(RuntimeError::WrongFileMode(name.to_string())),
+            None => Err(RuntimeError::FileNotOpen(name.to_string())),
+        }
+    }
+
+    fn random(&mut self, name: &str) -> Result<&mut RandomFile, RuntimeError> {
+        match self.open.get_mut(name) {
+            Some(OpenFile::Random(random)) => Ok(random),
+            Some(_) => Err(RuntimeError::WrongFileMode(name.to_string())),
+            None => Err(RuntimeError::FileNotOpen(name.to_string())),
+        }
+    }
+
+    pub fn read_line(&mut self, name: &str) -> Result<String, RuntimeError> {
+        let mut line = String::new();
+        if self.reader(name)?.read_line(&mut line)? == 0 {
+            return Err(RuntimeError::EndOfFile(name.to_string()));
+        }
+        let trimmed_len = line.trim_end_matches(['\r', '\n']).len();
+        line.truncate(trimmed_len);
+        Ok(line)
+    }
+
+    pub fn write_line(&mut self, name: &str, line: &str) -> Result<(), RuntimeError> {
+        match self.open.get_mut(name) {
+            Some(OpenFile::Writer(writer)) => Ok(writeln!(writer, "{line}")?),
+            Some(_) => Err(RuntimeError::WrongFileMode(name.to_string())),
+            None => Err(RuntimeError::FileNotOpen(name.to_string())),
+        }
+    }
+
+    /// Moves to a record in a random file. Addresses start at 1.
+    pub fn seek(&mut self, name: &str, address: i64) -> Result<(), RuntimeError> {
+        let random = self.random(name)?;
+        random.record = match u64::try_from(address) {
+            Ok(address @ 1..) => address - 1,
+            _ => return Err(RuntimeError::InvalidRecordAddress(address)),
+        };
+        Ok(())
+    }
+
+    pub fn get_record(&mut self, name: &str, type_: &ValueType) -> Result<Option<Value>, RuntimeError> {
+        let random = self.random(name)?;
+        let mut bytes = vec![0; random.next_record(type_)?];
+        match random.file.read_exact(&mut bytes) {
+            Err(error) if error.kind() == std::io::ErrorKind::UnexpectedEof => {
+                return Err(RuntimeError::EndOfFile(name.to_string()));
+            }
+            result => result?,
+        }
+        decode(&mut bytes.as_slice(), type_)
+    }
+
+    pub fn put_record(&mut self, name: &str, value: &Option<Value>, type_: &ValueType) -> Result<(), RuntimeError> {
+        let random = self.random(name)?;
+        let mut bytes = Vec::new();
+        encode(value, type_, &mut bytes)?;
+        random.next_record(type_)?;
+        Ok(random.file.write_all(&bytes)?)
+    }
+
+    pub fn eof(&mut self, name: &str) -> Result<bool, RuntimeError> {
+        if let Some(OpenFile::Random(random)) = self.open.get(name) {
+            let len = random.file.metadata()?.len();
+            return match random.record_size {
+                Some(size) => Ok(random.record * size >= len),
+                // Whether there is a first record does not depend on its size
+                None if random.record == 0 => Ok(len == 0),
+                None => Err(RuntimeError::UnknownRecordSize(name.to_string())),
+            };
+        }
+        Ok(self.reader(name)?.fill_buf()?.is_empty())
+    }
+
+    pub fn close(&mut self, name: &str) -> Result<(), RuntimeError> {
+        match self.open.remove(name) {
+            Some(OpenFile::Writer(mut writer)) => Ok(writer.flush()?),
+            Some(OpenFile::Reader(_) | OpenFile::Random(_)) => Ok(()),
+            None => Err(RuntimeError::FileNotOpen(name.to_string())),
+        }
+    }
+}
@@ -0,0 +1,1439 @@
+mod files;
+mod serialize;
+mod value;
+
+pub use value::*;
+
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+use std::path::PathBuf;
+use std::rc::Rc;
+
+use crate::ast::*;
+use crate::scanner::Span;
+use files::Files;
+
+/// The deepest that calls to procedures, functions and methods can be nested. Each call takes a
+/// few tens of kilobytes of native stack in a debug build, so a program that recurses this far
+/// needs to be run on a thread with a large stack, as the binary does.
+pub const MAX_CALL_DEPTH: usize = 1000;
+
+#[derive(Debug)]
+pub enum RuntimeError {
+    UndeclaredVariable(usize),
+    UnassignedVariable(usize),
+    AssignToConstant(usize),
+    ReferenceMismatch { expected: ValueType, found: ValueType },
+    UndefinedRoutine(usize),
+    UndefinedType(usize),
+    UndefinedField(usize),
+    UndefinedClass(usize),
+    PrivateMember(usize),
+    NoSuperclass,
+    NotAProcedure(usize),
+    NotAFunction(usize),
+    MissingReturn(usize),
+    UnexpectedReturn,
+    /// Calls were nested more deeply than `MAX_CALL_DEPTH`, as by a recursive routine that never stops
+    StackOverflow,
+    ArgumentCount { expected: usize, found: usize },
+    TypeMismatch { expected: ValueType, found: Value },
+    InvalidOperands(BinaryOperator, Value, Value),
+    InvalidOperand(UnaryOperator, Value),
+    InvalidTarget,
+    InvalidBounds(i64, i64),
+    IndexOutOfBounds { index: i64, bounds: (i64, i64) },
+    DimensionMismatch { expected: usize, found: usize },
+    NotAnArray(Value),
+    NotARecord(Value),
+    NotAPointer(Value),
+    NotAnObject(Value),
+    InvalidStep,
+    DivisionByZero,
+    Overflow,
+    InvalidInput(String),
+    EndOfInput,
+    InvalidFileName(String),
+    FileAlreadyOpen(String),
+    FileNotOpen(String),
+    WrongFileMode(String),
+    EndOfFile(String),
+    /// EOF was used on a random file after a SEEK, but before any record was read or written
+    UnknownRecordSize(String),
+    InvalidRecordAddress(i64),
+    InvalidRecord,
+    NotStorable(ValueType),
+    StringTooLong(Rc<str>),
+    Io(io::Error),
+}
+
+impl From<io::Error> for RuntimeError {
+    fn from(error: io::Error) -> Self {
+        RuntimeError::Io(error)
+    }
+}
+
+#[derive(Debug)]
+struct Variable {
+    type_: ValueType,
+    value: Option<Value>,
+    constant: bool,
+}
+
+#[derive(Debug)]
+struct Routine {
+    params: Vec<Parameter>,
+    return_type: Option<ValueType>,
+    body: Block,
+}
+
+/// A class declared with CLASS … ENDCLASS. Inherited members are found through its parent.
+#[derive(Debug)]
+struct Class {
+    type_: Rc<ClassType>,
+    attributes: Vec<(usize, ValueType, Visibility)>,
+    methods: HashMap<usize, (Rc<Routine>, Visibility)>,
+}
+
+#[derive(Debug)]
+struct Object {
+    class: Rc<ClassType>,
+    attributes: Scope,
+}
+
+/// The object a method was called on, and the class the method was declared in.
+#[derive(Clone, Copy, Debug)]
+struct Receiver {
+    object: usize,
+    class: usize,
+}
+
+#[derive(Debug)]
+struct Frame {
+    variables: Scope,
+    receiver: Option<Receiver>,
+}
+
+#[derive(Debug)]
+enum Binding {
+    Variable(Variable),
+    /// A BYREF parameter, standing in for a location in a caller's scope
+    Reference(Place),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ScopeId {
+    Global,
+    Frame(usize),
+    Object(usize),
+}
+
+/// A storage location that can be read from or assigned to.
+#[derive(Clone, Debug, PartialEq)]
+struct Place {
+    scope: ScopeId,
+    handle: usize,
+    path: Vec<Accessor>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Accessor {
+    Index(Vec<i64>),
+    Field(usize),
+}
+
+/// A resolved storage location, along with the type its contents must have.
+struct Slot<'a> {
+    value: &'a mut Option<Value>,
+    type_: &'a ValueType,
+}
+
+enum Flow {
+    Continue,
+    Return(Value),
+}
+
+type Scope = HashMap<usize, Binding>;
+
+pub struct Interpreter<R, W> {
+    input: R,
+    output: W,
+    globals: Scope,
+    frames: Vec<Frame>,
+    routines: HashMap<usize, Rc<Routine>>,
+    types: HashMap<usize, ValueType>,
+    classes: HashMap<usize, Class>,
+    objects: Vec<Object>,
+    files: Files,
+    /// The innermost statement or expression that was running when the last error happened
+    error_span: Option<Span>,
+}
+
+fn identifier_handle(expr: &Expr) -> Result<usize, RuntimeError> {
+    match &expr.kind {
+        ExprKind::Identifier { handle, .. } => Ok(*handle),
+        _ => Err(RuntimeError::InvalidTarget),
+    }
+}
+
+impl<R: BufRead, W: Write> Interpreter<R, W> {
+    pub fn new(input: R, output: W) -> Self {
+        Interpreter {
+            input,
+            output,
+            globals: HashMap::new(),
+            frames: Vec::new(),
+            routines: HashMap::new(),
+            types: HashMap::new(),
+            classes: HashMap::new(),
+            objects: Vec::new(),
+            files: Files::new(PathBuf::from(".")),
+            error_span: None,
+        }
+    }
+
+    /// Sets the directory that file names used by OPENFILE are resolved against.
+    pub fn with_file_root(mut self, root: impl Into<PathBuf>) -> Self {
+        self.files = Files::new(root.into());
+        self
+    }
+
+    /// The stream that INPUT reads from.
+    pub fn input(&mut self) -> &mut R {
+        &mut self.input
+    }
+
+    /// The stream that OUTPUT writes to.
+    pub fn output(&mut self) -> &mut W {
+        &mut self.output
+    }
+
+    /// The global variables and constants, with their values if they have been assigned.
+    pub fn globals(&self) -> impl Iterator<Item = (usize, Option<&Value>)> {
+        self.globals.iter().filter_map(|(handle, binding)| match binding {
+            Binding::Variable(variable) => Some((*handle, variable.value.as_ref())),
+            Binding::Reference(_) => None,
+        })
+    }
+
+    /// Where the last error from `run` or `evaluate_expression` happened: the innermost statement
+    /// that failed, or the whole expression.
+    pub fn error_span(&self) -> Option<Span> {
+        self.error_span
+    }
+
+    /// Evaluates an expression outside of any routine, as if it were part of the main program.
+    pub fn evaluate_expression(&mut self, expr: &Expr) -> Result<Value, RuntimeError> {
+        self.error_span = None;
+        self.evaluate(expr).inspect_err(|_| self.error_span = Some(expr.span))
+    }
+
+    pub fn run(&mut self, block: &Block) -> Result<(), RuntimeError> {
+        self.error_span = None;
+        // Routines may be called before the point where they are declared
+        for stmt in &block.contents {
+            if matches!(stmt.kind, StmtKind::ProcedureDecl { .. } | StmtKind::FunctionDecl { .. }) {
+                self.execute_traced(stmt)?;
+            }
+        }
+        for stmt in &block.contents {
+            if let Flow::Return(_) = self.execute_traced(stmt)? {
+                self.error_span = Some(stmt.span);
+                return Err(RuntimeError::UnexpectedReturn);
+            }
+        }
+        Ok(())
+    }
+
+    fn execute_block(&mut self, block: &Block) -> Result<Flow, RuntimeError> {
+        for stmt in &block.contents {
+            if let Flow::Return(value) = self.execute_traced(stmt)? {
+                return Ok(Flow::Return(value));
+            }
+        }
+        Ok(Flow::Continue)
+    }
+
+    /// Executes `stmt`, recording its span if it fails and no statement inside it already has.
+    fn execute_traced(&mut self, stmt: &Stmt) -> Result<Flow, RuntimeError> {
+        self.execute(stmt).inspect_err(|_| {
+            self.error_span.get_or_insert(stmt.span);
+        })
+    }
+
+    fn execute(&mut self, stmt: &Stmt) -> Result<Flow, RuntimeError> {
+        match &stmt.kind {
+            StmtKind::ProcedureDecl { .. } | StmtKind::FunctionDecl { .. } => {
+                let (handle, routine) = self.routine_decl(stmt)?;
+                self.routines.insert(handle, Rc::new(routine));
+            }
+            StmtKind::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                if self.evaluate(condition)?.as_boolean()? {
+                    return self.execute_block(then_branch);
+                } else if let Some(else_branch) = else_branch {
+                    return self.execute_block(else_branch);
+                }
+            }
+            StmtKind::CaseOf {
+                condition,
+                cases,
+                otherwise,
+            } => {
+                let value = self.evaluate(condition)?;
+                for (label, body) in cases {
+                    let (start, end) = label.bounds();
+                    if binary(BinaryOperator::GreaterEqual, value.clone(), start.into())?.as_boolean()?
+                        && binary(BinaryOperator::LessEqual, value.clone(), end.into())?.as_boolean()?
+                    {
+                        return self.execute_block(body);
+                    }
+                }
+                if let Some(otherwise) = otherwise {
+                    return self.execute_block(otherwise);
+                }
+            }
+            StmtKind::ForLoop {
+                target,
+                start,
+                end,
+                step,
+                body,
+            } => {
+                let handle = identifier_handle(target)?;
+                if self.lookup(handle).is_err() {
+                    // Loop counters are commonly used without being declared
+                    self.declare(handle, ValueType::Primitive(PrimitiveType::Integer), None, false);
+                }
+                let mut counter = self.evaluate(start)?.as_integer()?;
+                let end = self.evaluate(end)?.as_integer()?;
+                let step = match step {
+                    Some(step) => self.evaluate(step)?.as_integer()?,
+                    None => 1,
+                };
+                if step == 0 {
+                    return Err(RuntimeError::InvalidStep);
+                }
+                while (step > 0 && counter <= end) || (step < 0 && counter >= end) {
+                    self.assign(target, Value::Integer(counter))?;
+                    if let Flow::Return(value) = self.execute_block(body)? {
+                        return Ok(Flow::Return(value));
+                    }
+                    counter = counter.checked_add(step).ok_or(RuntimeError::Overflow)?;
+                }
+            }
+            StmtKind::RepeatUntil { body, condition } => loop {
+                if let Flow::Return(value) = self.execute_block(body)? {
+                    return Ok(Flow::Return(value));
+                }
+                if self.evaluate(condition)?.as_boolean()? {
+                    break;
+                }
+            },
+            StmtKind::While { condition, body } => {
+                while self.evaluate(condition)?.as_boolean()? {
+                    if let Flow::Return(value) = self.execute_block(body)? {
+                        return Ok(Flow::Return(value));
+                    }
+                }
+            }
+            StmtKind::VariableDecl { name, type_ } => {
+                let handle = identifier_handle(name)?;
+                let type_ = self.resolve_type(type_)?;
+                let value = Value::initial(&type_)?;
+                self.declare(handle, type_, value, false);
+            }
+            StmtKind::RecordDecl { name, fields } => {
+                let handle = identifier_handle(name)?;
+                let fields = fields
+                    .iter()
+                    .map(|field| Ok((identifier_handle(&field.name)?, self.resolve_type(&field.type_)?)))
+                    .collect::<Result<_, RuntimeError>>()?;
+                let record_type = RecordType { name: handle, fields };
+                self.types.insert(handle, ValueType::Record(Rc::new(record_type)));
+            }
+            StmtKind::ClassDecl { name, parent, members } => {
+                let handle = identifier_handle(name)?;
+                let parent = match parent {
+                    Some(parent) => {
+                        let parent = identifier_handle(parent)?;
+                        match self.classes.get(&parent) {
+                            Some(class) => Some(Rc::clone(&class.type_)),
+                            None => return Err(RuntimeError::UndefinedClass(parent)),
+                        }
+                    }
+                    None => None,
+                };
+                let type_ = Rc::new(ClassType { name: handle, parent });
+                // Attributes may refer to the class being declared
+                self.types.insert(handle, ValueType::Class(Rc::clone(&type_)));
+                let mut class = Class {
+                    type_,
+                    attributes: Vec::new(),
+                    methods: HashMap::new(),
+                };
+                for member in members {
+                    match member {
+                        Member::Attribute { visibility, field } => {
+                            let type_ = self.resolve_type(&field.type_)?;
+                            class.attributes.push((identifier_handle(&field.name)?, type_, *visibility));
+                        }
+                        Member::Method { visibility, decl } => {
+                            let (handle, routine) = self.routine_decl(decl)?;
+                            class.methods.insert(handle, (Rc::new(routine), *visibility));
+                        }
+                    }
+                }
+                self.classes.insert(handle, class);
+            }
+            StmtKind::TypeDecl { name, type_: Type::Enumerated(values) } => {
+                let handle = identifier_handle(name)?;
+                let values = values.iter().map(identifier_handle).collect::<Result<_, _>>()?;
+                let enum_type = Rc::new(EnumType { name: handle, values });
+                // Each value becomes a constant in the scope the type is declared in
+                for (ordinal, &value) in enum_type.values.iter().enumerate() {
+                    let type_ = ValueType::Enum(Rc::clone(&enum_type));
+                    self.declare(value, type_, Some(Value::Enum(Rc::clone(&enum_type), ordinal)), true);
+                }
+                self.types.insert(handle, ValueType::Enum(enum_type));
+            }
+            StmtKind::TypeDecl { name, type_ } => {
+                let handle = identifier_handle(name)?;
+                let type_ = self.resolve_type(type_)?;
+                self.types.insert(handle, type_);
+            }
+            StmtKind::ConstantDecl { name, value } => {
+                let handle = identifier_handle(name)?;
+                let value = Value::from(value);
+                self.declare(handle, value.type_(), Some(value), true);
+            }
+            StmtKind::Input(targets) => {
+                for target in targets {
+                    let place = self.resolve_place(target)?;
+                    let line = self.read_line()?;
+                    self.store_line(&place, line)?;
+                }
+            }
+            StmtKind::Output(values) => {
+                for value in values {
+                    let value = self.evaluate(value)?;
+                    write!(self.output, "{value}")?;
+                }
+                writeln!(self.output)?;
+            }
+            StmtKind::Return(value) => return Ok(Flow::Return(self.evaluate(value)?)),
+            StmtKind::FileOpen { file, mode } => {
+                let name = self.file_name(file)?;
+                self.files.open(&name, *mode)?;
+            }
+            StmtKind::FileRead { file, target } => {
+                let name = self.file_name(file)?;
+                let place = self.resolve_place(target)?;
+                let line = self.files.read_line(&name)?;
+                self.store_line(&place, line)?;
+            }
+            StmtKind::FileWrite { file, value } => {
+                let name = self.file_name(file)?;
+                let value = self.evaluate(value)?;
+                self.files.write_line(&name, &value.to_string())?;
+            }
+            StmtKind::FileClose { file } => {
+                let name = self.file_name(file)?;
+                self.files.close(&name)?;
+            }
+            StmtKind::FileSeek { file, address } => {
+                let name = self.file_name(file)?;
+                let address = self.evaluate(address)?.as_integer()?;
+                self.files.seek(&name, address)?;
+            }
+            StmtKind::FileGetRecord { file, target } => {
+                let name = self.file_name(file)?;
+                let place = self.resolve_place(target)?;
+                if self.variable_mut(&place)?.constant {
+                    return Err(RuntimeError::AssignToConstant(place.handle));
+                }
+                let type_ = self.slot(&place)?.type_.clone();
+                let value = self.files.get_record(&name, &type_)?;
+                *self.slot(&place)?.value = value;
+            }
+            StmtKind::FilePutRecord { file, source } => {
+                let name = self.file_name(file)?;
+                let place = self.resolve_place(source)?;
+                let slot = self.slot(&place)?;
+                let (value, type_) = (slot.value.clone(), slot.type_.clone());
+                self.files.put_record(&name, &value, &type_)?;
+            }
+            StmtKind::Procedure { name, args } => {
+                self.call_procedure(name, args.as_deref().unwrap_or_default())?;
+            }
+            StmtKind::Assignment { target, value } => {
+                let value = self.evaluate(value)?;
+                self.assign(target, value)?;
+            }
+        };
+        Ok(Flow::Continue)
+    }
+
+    fn evaluate(&mut self, expr: &Expr) -> Result<Value, RuntimeError> {
+        match &expr.kind {
+            ExprKind::Binary {
+                left,
+                operator,
+                right,
+            } => {
+                let left = self.evaluate(left)?;
+                // AND and OR short-circuit
+                match (operator, &left) {
+                    (BinaryOperator::LogicAnd, Value::Boolean(false)) => return Ok(left),
+                    (BinaryOperator::LogicOr, Value::Boolean(true)) => return Ok(left),
+                    _ => {}
+                }
+                let right = self.evaluate(right)?;
+                binary(*operator, left, right)
+            }
+            ExprKind::Unary { operator, right } => {
+                let right = self.evaluate(right)?;
+                unary(*operator, right)
+            }
+            ExprKind::FunctionCall { function, args } => self.call_function(function, args),
+            ExprKind::AddressOf { target } => {
+                let place = self.resolve_place(target)?;
+                let target = self.slot(&place)?.type_.by_name();
+                Ok(Value::Pointer(Box::new(Pointer { target, place })))
+            }
+            ExprKind::ArrayIndex { .. } | ExprKind::FieldAccess { .. } | ExprKind::Dereference { .. } | ExprKind::Identifier { .. } => {
+                let place = self.resolve_place(expr)?;
+                self.slot(&place)?
+                    .value
+                    .clone()
+                    .ok_or(RuntimeError::UnassignedVariable(place.handle))
+            }
+            ExprKind::New { class, args } => self.instantiate(class, args),
+            // SUPER can only be used to call a method
+            ExprKind::Super => Err(RuntimeError::InvalidTarget),
+            ExprKind::Literal(literal) => Ok(literal.into()),
+        }
+    }
+
+    /// Calls a procedure from a CALL statement. Functions cannot be called this way.
+    fn call_procedure(&mut self, name: &Expr, args: &[Expr]) -> Result<(), RuntimeError> {
+        if let ExprKind::Identifier { handle, .. } = &name.kind {
+            if Builtin::from_handle(*handle).is_some() {
+                return Err(RuntimeError::NotAProcedure(*handle));
+            }
+        }
+        let (handle, routine, receiver) = self.routine(name)?;
+        if routine.return_type.is_some() {
+            return Err(RuntimeError::NotAProcedure(handle));
+        }
+        self.invoke(handle, &routine, args, receiver)?;
+        Ok(())
+    }
+
+    /// Calls a function from within an expression. Procedures cannot be called this way.
+    fn call_function(&mut self, function: &Expr, args: &[Expr]) -> Result<Value, RuntimeError> {
+        if let ExprKind::Identifier { handle, .. } = &function.kind {
+            if let Some(builtin) = Builtin::from_handle(*handle) {
+                return self.call_builtin(builtin, args);
+            }
+        }
+        let (handle, routine, receiver) = self.routine(function)?;
+        if routine.return_type.is_none() {
+            return Err(RuntimeError::NotAFunction(handle));
+        }
+        self.invoke(handle, &routine, args, receiver)?
+            .ok_or(RuntimeError::MissingReturn(handle))
+    }
+
+    /// Finds the routine that `name` calls. Through an object or SUPER this is a method, and
+    /// inside a method a bare name can also refer to another method of the same object.
+    fn routine(&mut self, name: &Expr) -> Result<(usize, Rc<Routine>, Option<Receiver>), RuntimeError> {
+        let (handle, object, class_type) = match &name.kind {
+            ExprKind::FieldAccess { object, field } => {
+                let handle = identifier_handle(field)?;
+                match &object.kind {
+                    ExprKind::Super => {
+                        let receiver = self.receiver().ok_or(RuntimeError::NoSuperclass)?;
+                        let parent = self.classes[&receiver.class].type_.parent.clone();
+                        (handle, receiver.object, parent.ok_or(RuntimeError::NoSuperclass)?)
+                    }
+                    _ => match self.evaluate(object)? {
+                        Value::Object(class_type, object) => (handle, object, class_type),
+                        other => return Err(RuntimeError::NotAnObject(other)),
+                    },
+                }
+            }
+            _ => {
+                let handle = identifier_handle(name)?;
+                let own_method = self
+                    .receiver()
+                    .map(|receiver| (receiver.object, Rc::clone(&self.objects[receiver.object].class)))
+                    .filter(|(_, class_type)| self.method(class_type, handle).is_some());
+                match own_method {
+                    Some((object, class_type)) => (handle, object, class_type),
+                    None => {
+                        return match self.routines.get(&handle) {
+                            Some(routine) => Ok((handle, Rc::clone(routine), None)),
+                            None => Err(RuntimeError::UndefinedRoutine(handle)),
+                        }
+                    }
+                }
+            }
+        };
+        let (owner, (routine, visibility)) =
+            self.method(&class_type, handle).ok_or(RuntimeError::UndefinedRoutine(handle))?;
+        self.check_access(owner, visibility, handle)?;
+        Ok((handle, routine, Some(Receiver { object, class: owner })))
+    }
+
+    /// Creates an object of `class` and runs its constructor, which may be inherited.
+    fn instantiate(&mut self, class: &Expr, args: &[Expr]) -> Result<Value, RuntimeError> {
+        let handle = identifier_handle(class)?;
+        let class_type = match self.classes.get(&handle) {
+            Some(class) => Rc::clone(&class.type_),
+            None => return Err(RuntimeError::UndefinedClass(handle)),
+        };
+        let mut attributes = Scope::new();
+        let mut current = Some(&*class_type);
+        while let Some(class_type) = current {
+            for (attribute, type_, _) in &self.classes[&class_type.name].attributes {
+                let variable = Variable {
+                    type_: type_.clone(),
+                    value: Value::initial(type_)?,
+                    constant: false,
+                };
+                attributes.entry(*attribute).or_insert(Binding::Variable(variable));
+            }
+            current = class_type.parent.as_deref();
+        }
+        let object = self.objects.len();
+        self.objects.push(Object {
+            class: Rc::clone(&class_type),
+            attributes,
+        });
+        match self.method(&class_type, CONSTRUCTOR) {
+            Some((owner, (routine, visibility))) => {
+                self.check_access(owner, visibility, CONSTRUCTOR)?;
+                self.invoke(CONSTRUCTOR, &routine, args, Some(Receiver { object, class: owner }))?;
+            }
+            None if !args.is_empty() => {
+                return Err(RuntimeError::ArgumentCount {
+                    expected: 0,
+                    found: args.len(),
+                })
+            }
+            None => {}
+        }
+        Ok(Value::Object(class_type, object))
+    }
+
+    /// Finds a method of `class_type` or of a class it inherits from, along with the class
+    /// that declares it.
+    fn method(&self, class_type: &ClassType, handle: usize) -> Option<(usize, (Rc<Routine>, Visibility))> {
+        self.find_member(class_type, |class| class.methods.get(&handle).cloned())
+    }
+
+    fn find_member<T>(&self, class_type: &ClassType, find: impl Fn(&Class) -> Option<T>) -> Option<(usize, T)> {
+        let mut current = Some(class_type);
+        while let Some(class_type) = current {
+            if let Some(member) = find(&self.classes[&class_type.name]) {
+                return Some((class_type.name, member));
+            }
+            current = class_type.parent.as_deref();
+        }
+        None
+    }
+
+    fn check_attribute_access(&self, class_type: &ClassType, handle: usize) -> Result<(), RuntimeError> {
+        let (owner, visibility) = self
+            .find_member(class_type, |class| {
+                class.attributes.iter().find(|(attribute, ..)| *attribute == handle).map(|(.., visibility)| *visibility)
+            })
+            .ok_or(RuntimeError::UndefinedField(handle))?;
+        self.check_access(owner, visibility, handle)
+    }
+
+    /// Private members can only be used by methods of the class that declares them.
+    fn check_access(&self, owner: usize, visibility: Visibility, handle: usize) -> Result<(), RuntimeError> {
+        match (visibility, self.receiver()) {
+            (Visibility::Private, Some(receiver)) if receiver.class == owner => Ok(()),
+            (Visibility::Private, _) => Err(RuntimeError::PrivateMember(handle)),
+            (Visibility::Public, _) => Ok(()),
+        }
+    }
+
+    fn receiver(&self) -> Option<Receiver> {
+        self.frames.last().and_then(|frame| frame.receiver)
+    }
+
+    fn invoke(
+        &mut self,
+        handle: usize,
+        routine: &Routine,
+        args: &[Expr],
+        receiver: Option<Receiver>,
+    ) -> Result<Option<Value>, RuntimeError> {
+        if self.frames.len() >= MAX_CALL_DEPTH {
+            return Err(RuntimeError::StackOverflow);
+        }
+        if args.len() != routine.params.len() {
+            return Err(RuntimeError::ArgumentCount {
+                expected: routine.params.len(),
+                found: args.len(),
+            });
+        }
+
+        let mut frame = Scope::new();
+        for (param, arg) in routine.params.iter().zip(args) {
+            let type_ = self.resolve_type(&param.type_)?;
+            let binding = match param.mode {
+                PassingMode::ByValue => Binding::Variable(Variable {
+                    value: Some(self.evaluate(arg)?.coerce(&type_)?),
+                    type_,
+                    constant: false,
+                }),
+                PassingMode::ByReference => {
+                    let place = self.resolve_place(arg)?;
+                    let found = self.slot(&place)?.type_;
+                    if *found != type_ {
+                        return Err(RuntimeError::ReferenceMismatch {
+                            expected: type_,
+                            found: found.clone(),
+                        });
+                    }
+                    Binding::Reference(place)
+                }
+            };
+            frame.insert(identifier_handle(&param.name)?, binding);
+        }
+
+        self.frames.push(Frame {
+            variables: frame,
+            receiver,
+        });
+        let result = self.execute_block(&routine.body);
+        self.frames.pop();
+
+        match (result?, &routine.return_type) {
+            (Flow::Return(value), Some(type_)) => Ok(Some(value.coerce(type_)?)),
+            (Flow::Return(_), None) => Err(RuntimeError::UnexpectedReturn),
+            (Flow::Continue, Some(_)) => Err(RuntimeError::MissingReturn(handle)),
+            (Flow::Continue, None) => Ok(None),
+        }
+    }
+
+    fn call_builtin(&mut self, builtin: Builtin, args: &[Expr]) -> Result<Value, RuntimeError> {
+        match (builtin, args) {
+            (Builtin::Eof, [file]) => {
+                let name = self.file_name(file)?;
+                Ok(Value::Boolean(self.files.eof(&name)?))
+            }
+            (Builtin::Eof, _) => Err(RuntimeError::ArgumentCount {
+                expected: 1,
+                found: args.len(),
+            }),
+        }
+    }
+
+    fn file_name(&mut self, file: &Expr) -> Result<String, RuntimeError> {
+        match self.evaluate(file)? {
+            Value::String(name) => Ok(name.to_string()),
+            other => Err(RuntimeError::TypeMismatch {
+                expected: ValueType::Primitive(PrimitiveType::String),
+                found: other,
+            }),
+        }
+    }
+
+    /// Builds the routine declared by a PROCEDURE or FUNCTION statement.
+    fn routine_decl(&mut self, decl: &Stmt) -> Result<(usize, Routine), RuntimeError> {
+        let (name, params, return_type, body) = match &decl.kind {
+            StmtKind::ProcedureDecl { name, params, body } => (name, params, None, body),
+            StmtKind::FunctionDecl {
+                name,
+                params,
+                return_type,
+                body,
+            } => (name, params, Some(self.resolve_type(return_type)?), body),
+            _ => unreachable!("routines are declared with PROCEDURE or FUNCTION"),
+        };
+        let routine = Routine {
+            params: params.clone().unwrap_or_default(),
+            return_type,
+            body: body.clone(),
+        };
+        Ok((identifier_handle(name)?, routine))
+    }
+
+    fn resolve_type(&mut self, type_: &Type) -> Result<ValueType, RuntimeError> {
+        match type_ {
+            Type::Primitive(primitive) => Ok(ValueType::Primitive(*primitive)),
+            Type::Array(ArrayType { inner_type, ranges }) => {
+                let mut bounds = Vec::new();
+                for (lower, upper) in ranges {
+                    let lower = self.evaluate(lower)?.as_integer()?;
+                    let upper = self.evaluate(upper)?.as_integer()?;
+                    bounds.push((lower, upper));
+                }
+                Ok(ValueType::Array {
+                    element_type: Box::new(self.resolve_type(inner_type)?),
+                    bounds,
+                })
+            }
+            Type::Named(name) => {
+                let handle = identifier_handle(name)?;
+                self.types.get(&handle).cloned().ok_or(RuntimeError::UndefinedType(handle))
+            }
+            Type::Pointer(target) => {
+                let target = match &**target {
+                    // The target may not have been declared yet
+                    Type::Named(name) => {
+                        let handle = identifier_handle(name)?;
+                        self.types.get(&handle).map_or(ValueType::Named(handle), ValueType::by_name)
+                    }
+                    target => self.resolve_type(target)?.by_name(),
+                };
+                Ok(ValueType::Pointer(Box::new(target)))
+            }
+            Type::Enumerated(_) => unreachable!("enumerated types are only parsed in TYPE declarations"),
+        }
+    }
+
+    fn assign(&mut self, target: &Expr, value: Value) -> Result<(), RuntimeError> {
+        let place = self.resolve_place(target)?;
+        self.store(&place, value)
+    }
+
+    fn store(&mut self, place: &Place, value: Value) -> Result<(), RuntimeError> {
+        if self.variable_mut(place)?.constant {
+            return Err(RuntimeError::AssignToConstant(place.handle));
+        }
+        let slot = self.slot(place)?;
+        *slot.value = Some(value.coerce(slot.type_)?);
+        Ok(())
+    }
+
+    /// Converts a line read from the user or a file to the type of `place` and stores it there.
+    fn store_line(&mut self, place: &Place, line: String) -> Result<(), RuntimeError> {
+        let type_ = match self.slot(place)?.type_ {
+            ValueType::Primitive(primitive) => *primitive,
+            _ => return Err(RuntimeError::InvalidTarget),
+        };
+        let value = Value::parse(&line, type_).ok_or(RuntimeError::InvalidInput(line))?;
+        self.store(place, value)
+    }
+
+    /// Evaluates any indexes in `expr` to find the storage location it refers to.
+    fn resolve_place(&mut self, expr: &Expr) -> Result<Place, RuntimeError> {
+        match &expr.kind {
+            ExprKind::Identifier { handle, .. } => match self.lookup(*handle)? {
+                (_, Binding::Reference(place)) => Ok(place.clone()),
+                (scope, Binding::Variable(_)) => Ok(Place {
+                    scope,
+                    handle: *handle,
+                    path: Vec::new(),
+                }),
+            },
+            ExprKind::ArrayIndex { array, indexes } => {
+                let mut place = self.resolve_place(array)?;
+                let indexes = indexes
+                    .iter()
+                    .map(|index| self.evaluate(index)?.as_integer())
+                    .collect::<Result<_, _>>()?;
+                place.path.push(Accessor::Index(indexes));
+                Ok(place)
+            }
+            ExprKind::FieldAccess { object, field } => {
+                let mut place = self.resolve_place(object)?;
+                let field = identifier_handle(field)?;
+                // Attributes of an object live in the object rather than in the variable
+                if let Some(Value::Object(class_type, object)) = &self.slot(&place)?.value {
+                    let (class_type, object) = (Rc::clone(class_type), *object);
+                    self.check_attribute_access(&class_type, field)?;
+                    return Ok(Place {
+                        scope: ScopeId::Object(object),
+                        handle: field,
+                        path: Vec::new(),
+                    });
+                }
+                place.path.push(Accessor::Field(field));
+                Ok(place)
+            }
+            ExprKind::Dereference { pointer } => match self.evaluate(pointer)? {
+                Value::Pointer(pointer) => Ok(pointer.place),
+                other => Err(RuntimeError::NotAPointer(other)),
+            },
+            _ => Err(RuntimeError::InvalidTarget),
+        }
+    }
+
+    fn slot(&mut self, place: &Place) -> Result<Slot<'_>, RuntimeError> {
+        let variable = self.variable_mut(place)?;
+        let mut slot = Slot {
+            value: &mut variable.value,
+            type_: &variable.type_,
+        };
+        for accessor in &place.path {
+            slot = match (accessor, slot.value) {
+                (Accessor::Index(indexes), Some(Value::Array(array))) => {
+                    let offset = array.offset(indexes)?;
+                    Slot {
+                        value: &mut array.elements[offset],
+                        type_: &array.element_type,
+                    }
+                }
+                (Accessor::Field(field), Some(Value::Record(record))) => {
+                    let record = &mut **record;
+                    let index = record.type_.field_index(*field).ok_or(RuntimeError::UndefinedField(*field))?;
+                    Slot {
+                        value: &mut record.fields[index],
+                        type_: &record.type_.fields[index].1,
+                    }
+                }
+                (_, None) => return Err(RuntimeError::UnassignedVariable(place.handle)),
+                (Accessor::Index(_), Some(other)) => return Err(RuntimeError::NotAnArray(other.clone())),
+                (Accessor::Field(_), Some(other)) => return Err(RuntimeError::NotARecord(other.clone())),
+            };
+        }
+        Ok(slot)
+    }
+
+    fn declare(&mut self, handle: usize, type_: ValueType, value: Option<Value>, constant: bool) {
+        let scope = match self.frames.last_mut() {
+            Some(frame) => &mut frame.variables,
+            None => &mut self.globals,
+        };
+        let variable = Variable {
+            type_,
+            value,
+            constant,
+        };
+        scope.insert(handle, Binding::Variable(variable));
+    }
+
+    /// Finds what `handle` is bound to, looking in the current frame before the globals.
+    fn lookup(&self, handle: usize) -> Result<(ScopeId, &Binding), RuntimeError> {
+        if let Some(index) = self.frames.len().checked_sub(1) {
+            let frame = &self.frames[index];
+            if let Some(binding) = frame.variables.get(&handle) {
+                return Ok((ScopeId::Frame(index), binding));
+            }
+            // Inside a method, the attributes of the object can be used by name
+            if let Some(receiver) = frame.receiver {
+                let object = &self.objects[receiver.object];
+                if let Some(binding) = object.attributes.get(&handle) {
+                    self.check_attribute_access(&object.class, handle)?;
+                    return Ok((ScopeId::Object(receiver.object), binding));
+                }
+            }
+        }
+        match self.globals.get(&handle) {
+            Some(binding) => Ok((ScopeId::Global, binding)),
+            None => Err(RuntimeError::UndeclaredVariable(handle)),
+        }
+    }
+
+    fn variable_mut(&mut self, place: &Place) -> Result<&mut Variable, RuntimeError> {
+        let scope = match place.scope {
+            ScopeId::Global => &mut self.globals,
+            // A pointer can outlive the frame it points into
+            ScopeId::Frame(index) => match self.frames.get_mut(index) {
+                Some(frame) => &mut frame.variables,
+                None => return Err(RuntimeError::UndeclaredVariable(place.handle)),
+            },
+            ScopeId::Object(object) => &mut self.objects[object].attributes,
+        };
+        match scope.get_mut(&place.handle) {
+            Some(Binding::Variable(variable)) => Ok(variable),
+            // Places are always resolved through references to the variable they point to
+            Some(Binding::Reference(_)) | None => Err(RuntimeError::UndeclaredVariable(place.handle)),
+        }
+    }
+
+    fn read_line(&mut self) -> Result<String, RuntimeError> {
+        self.output.flush()?;
+        let mut line = String::new();
+        if self.input.read_line(&mut line)? == 0 {
+            return Err(RuntimeError::EndOfInput);
+        }
+        let trimmed_len = line.trim_end_matches(['\r', '\n']).len();
+        line.truncate(trimmed_len);
+        Ok(line)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser;
+    use std::thread;
+
+    fn run(source: &str, input: &str) -> Result<String, RuntimeError> {
+        let (program, errors) = parser::parse_program(source);
+        assert!(errors.is_empty(), "{errors:?}");
+        let mut output = Vec::new();
+        Interpreter::new(input.as_bytes(), &mut output).run(&program.body)?;
+        Ok(String::from_utf8(output).unwrap())
+    }
+
+    #[test]
+    fn output_values() -> Result<(), RuntimeError> {
+        assert_eq!(run(r#"OUTPUT "x = ", 1 + 2 * 3, ' ', 7 / 2, TRUE"#, "")?, "x = 7 3.5TRUE\n");
+        Ok(())
+    }
+
+    #[test]
+    fn assignment_and_widening() -> Result<(), RuntimeError> {
+        let source = "
+            DECLARE X : REAL
+            X <- 2
+            OUTPUT X
+        ";
+        assert_eq!(run(source, "")?, "2.0\n");
+        assert!(matches!(
+            run("DECLARE X : INTEGER\nX <- \"hello\"", ""),
+            Err(RuntimeError::TypeMismatch {
+                expected: ValueType::Primitive(PrimitiveType::Integer),
+                ..
+            })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn loops() -> Result<(), RuntimeError> {
+        let source = "
+            DECLARE Total : INTEGER
+            Total <- 0
+            FOR I <- 10 TO 1 STEP -3
+                Total <- Total + I
+            NEXT I
+            WHILE Total < 30 DO
+                Total <- Total + 1
+            ENDWHILE
+            REPEAT
+                Total <- Total * 2
+            UNTIL Total > 100
+            OUTPUT Total
+        ";
+        assert_eq!(run(source, "")?, "120\n");
+        Ok(())
+    }
+
+    #[test]
+    fn input_conversion() -> Result<(), RuntimeError> {
+        let source = "
+            DECLARE Name : STRING
+            DECLARE Age : INTEGER
+            INPUT Name, Age
+            IF Age >= 18 THEN
+                OUTPUT Name, \" is an adult\"
+            ELSE
+                OUTPUT Name, \" is a child\"
+            ENDIF
+        ";
+        assert_eq!(run(source, "Ada\n36\n")?, "Ada is an adult\n");
+        assert!(matches!(run(source, "Ada\nold\n"), Err(RuntimeError::InvalidInput(_))));
+        assert!(matches!(run(source, "Ada\n"), Err(RuntimeError::EndOfInput)));
+        Ok(())
+    }
+
+    #[test]
+    fn recursive_function() -> Result<(), RuntimeError> {
+        let source = "
+            OUTPUT Factorial(10)
+            FUNCTION Factorial(N : INTEGER) RETURNS INTEGER
+                IF N <= 1 THEN
+                    RETURN 1
+                ENDIF
+                RETURN N * Factorial(N - 1)
+            ENDFUNCTION
+        ";
+        assert_eq!(run(source, "")?, "3628800\n");
+
+        // Run with room for the deepest calls allowed, as the test threads have small stacks
+        let endless = "PROCEDURE Forever\n    CALL Forever\nENDPROCEDURE\nCALL Forever";
+        let overflows = move || matches!(run(endless, ""), Err(RuntimeError::StackOverflow));
+        assert!(thread::Builder::new().stack_size(256 << 20).spawn(overflows).unwrap().join().unwrap());
+        Ok(())
+    }
+
+    #[test]
+    fn operators() -> Result<(), RuntimeError> {
+        let source = "
+            DECLARE X : INTEGER
+            X <- 7
+            OUTPUT X DIV 2, ' ', X MOD 3, ' ', - X DIV 2, ' ', -X
+            OUTPUT 2 ^ 10, ' ', 2 ^ 3 ^ 2, ' ', 2.0 ^ 2, ' ', 4 ^ 0.5, ' ', 2 ^ (0 - 1)
+            OUTPUT \"X = \" & 'x' & \"!\"
+            CONSTANT Offset <- - 2
+            OUTPUT X-1, ' ', X-Offset, ' ', (X)-1
+            OUTPUT 2 ^ -X, ' ', X ^ -1, ' ', -2 ^ 2
+        ";
+        let expected = "3 1 -3 -7\n1024.0 512.0 4.0 2.0 0.5\nX = x!\n6 9 6\n0.0078125 0.14285714285714285 -4.0\n";
+        assert_eq!(run(source, "")?, expected);
+
+        assert!(matches!(run("OUTPUT 1 MOD 0", ""), Err(RuntimeError::DivisionByZero)));
+        assert!(matches!(run("OUTPUT 9223372036854775807 + 1", ""), Err(RuntimeError::Overflow)));
+        assert_eq!(run("OUTPUT 2 ^ 64", "")?, "1.8446744073709552e19\n");
+        assert!(matches!(run("OUTPUT \"a\" & 1", ""), Err(RuntimeError::InvalidOperands(BinaryOperator::Concat, ..))));
+        assert!(matches!(run("OUTPUT - \"a\"", ""), Err(RuntimeError::InvalidOperand(UnaryOperator::Negate, _))));
+        Ok(())
+    }
+
+    #[test]
+    fn case_of() -> Result<(), RuntimeError> {
+        let source = "
+            DECLARE Score : INTEGER
+            INPUT Score
+            CASE OF Score
+                100 : OUTPUT \"Perfect\"
+                50 TO 99 : OUTPUT \"Pass\"
+                           OUTPUT \"Well done\"
+                -1 : OUTPUT \"Absent\"
+                OTHERWISE : OUTPUT \"Fail\"
+            ENDCASE
+        ";
+        assert_eq!(run(source, "100\n")?, "Perfect\n");
+        assert_eq!(run(source, "50\n")?, "Pass\nWell done\n");
+        assert_eq!(run(source, "-1\n")?, "Absent\n");
+        assert_eq!(run(source, "49\n")?, "Fail\n");
+        Ok(())
+    }
+
+    #[test]
+    fn arrays() -> Result<(), RuntimeError> {
+        let source = "
+            CONSTANT Size <- 3
+            DECLARE Grid : ARRAY[0:Size - 1, 1:Size] OF INTEGER
+            DECLARE Names : ARRAY[5:6] OF STRING
+            FOR Row <- 0 TO Size - 1
+                FOR Col <- 1 TO Size
+                    Grid[Row, Col] <- Row * Size + Col
+                NEXT Col
+            NEXT Row
+            INPUT Names[5], Names[6]
+            OUTPUT Grid[2, 3], ' ', Names[6]
+            OUTPUT Grid
+        ";
+        assert_eq!(run(source, "Ada\nAlan\n")?, "9 Alan\n[1, 2, 3, 4, 5, 6, 7, 8, 9]\n");
+        Ok(())
+    }
+
+    #[test]
+    fn array_errors() {
+        let declare = "DECLARE A : ARRAY[1:10] OF INTEGER\n";
+        assert!(matches!(
+            run(&format!("{declare}A[0] <- 1"), ""),
+            Err(RuntimeError::IndexOutOfBounds { index: 0, bounds: (1, 10) })
+        ));
+        assert!(matches!(
+            run(&format!("{declare}A[1, 1] <- 1"), ""),
+            Err(RuntimeError::DimensionMismatch { expected: 1, found: 2 })
+        ));
+        assert!(matches!(run(&format!("{declare}OUTPUT A[1]"), ""), Err(RuntimeError::UnassignedVariable(_))));
+        assert!(matches!(
+            run("DECLARE A : ARRAY[5:1] OF INTEGER", ""),
+            Err(RuntimeError::InvalidBounds(5, 1))
+        ));
+    }
+
+    #[test]
+    fn text_files() -> Result<(), RuntimeError> {
+        let root = std::env::temp_dir().join(format!("cambridgescript-text-files-{}", std::process::id()));
+        std::fs::create_dir_all(&root)?;
+        let source = "
+            DECLARE Line : STRING
+            OPENFILE \"numbers.txt\" FOR WRITE
+            WRITEFILE \"numbers.txt\", 1
+            WRITEFILE \"numbers.txt\", 2
+            CLOSEFILE \"numbers.txt\"
+            OPENFILE \"numbers.txt\" FOR APPEND
+            WRITEFILE \"numbers.txt\", 3 + 4
+            CLOSEFILE \"numbers.txt\"
+            OPENFILE \"numbers.txt\" FOR READ
+            WHILE NOT EOF(\"numbers.txt\") DO
+                READFILE \"numbers.txt\", Line
+                OUTPUT Line
+            ENDWHILE
+            CLOSEFILE \"numbers.txt\"
+        ";
+        let (program, _) = parser::parse_program(source);
+        let mut output = Vec::new();
+        Interpreter::new(io::empty(), &mut output).with_file_root(&root).run(&program.body)?;
+        assert_eq!(String::from_utf8(output).unwrap(), "1\n2\n7\n");
+        assert_eq!(std::fs::read_to_string(root.join("numbers.txt"))?, "1\n2\n7\n");
+        std::fs::remove_dir_all(&root)?;
+
+        assert!(matches!(run("CLOSEFILE \"a.txt\"", ""), Err(RuntimeError::FileNotOpen(_))));
+        assert!(matches!(
+            run("OPENFILE \"../a.txt\" FOR WRITE", ""),
+            Err(RuntimeError::InvalidFileName(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn random_files() -> Result<(), RuntimeError> {
+        let root = std::env::temp_dir().join(format!("cambridgescript-random-files-{}", std::process::id()));
+        std::fs::create_dir_all(&root)?;
+        let source = "
+            DECLARE Record : ARRAY[1:2] OF INTEGER
+            DECLARE Count : INTEGER
+            OPENFILE \"pupils.dat\" FOR RANDOM
+            FOR Address <- 3 TO 1 STEP -1
+                Record[1] <- Address
+                Record[2] <- Address * 10
+                SEEK \"pupils.dat\", Address
+                PUTRECORD \"pupils.dat\", Record
+            NEXT Address
+            SEEK \"pupils.dat\", 2
+            GETRECORD \"pupils.dat\", Record
+            OUTPUT Record[1], ' ', Record[2]
+            Count <- 0
+            SEEK \"pupils.dat\", 1
+            WHILE NOT EOF(\"pupils.dat\") DO
+                GETRECORD \"pupils.dat\", Record
+                Count <- Count + 1
+            ENDWHILE
+            OUTPUT Count
+            CLOSEFILE \"pupils.dat\"
+            OPENFILE \"pupils.dat\" FOR RANDOM
+            OPENFILE \"empty.dat\" FOR RANDOM
+            OUTPUT EOF(\"pupils.dat\"), ' ', EOF(\"empty.dat\")
+        ";
+        let run_in_root = |source: &str| {
+            let (program, _) = parser::parse_program(source);
+            let mut output = Vec::new();
+            Interpreter::new(io::empty(), &mut output).with_file_root(&root).run(&program.body)?;
+            Ok::<_, RuntimeError>(String::from_utf8(output).unwrap())
+        };
+        assert_eq!(run_in_root(source)?, "2 20\n3\nFALSE TRUE\n");
+        let seek = "OPENFILE \"pupils.dat\" FOR RANDOM\nSEEK \"pupils.dat\", 4\nOUTPUT EOF(\"pupils.dat\")";
+        assert!(matches!(run_in_root(seek), Err(RuntimeError::UnknownRecordSize(_))));
+        std::fs::remove_dir_all(&root)?;
+        Ok(())
+    }
+
+    #[test]
+    fn procedures() -> Result<(), RuntimeError> {
+        let source = "
+            PROCEDURE Greet(Name : STRING, Times : INTEGER)
+                FOR I <- 1 TO Times
+                    OUTPUT \"Hello, \", Name
+                NEXT I
+            ENDPROCEDURE
+            PROCEDURE Line
+                OUTPUT \"--\"
+            ENDPROCEDURE
+            FUNCTION Double(X : INTEGER) RETURNS INTEGER
+                RETURN X * 2
+            ENDFUNCTION
+            CALL Greet(\"Ada\", Double(1))
+            CALL Line
+            CALL Line()
+        ";
+        assert_eq!(run(source, "")?, "Hello, Ada\nHello, Ada\n--\n--\n");
+
+        let procedure = "PROCEDURE P\nENDPROCEDURE\n";
+        assert!(matches!(run(&format!("{procedure}OUTPUT P()"), ""), Err(RuntimeError::NotAFunction(_))));
+        let function = "FUNCTION F RETURNS INTEGER\nRETURN 1\nENDFUNCTION\n";
+        assert!(matches!(run(&format!("{function}CALL F"), ""), Err(RuntimeError::NotAProcedure(_))));
+        assert!(matches!(run("CALL EOF(\"a.txt\")", ""), Err(RuntimeError::NotAProcedure(_))));
+        Ok(())
+    }
+
+    #[test]
+    fn by_reference() -> Result<(), RuntimeError> {
+        let source = "
+            PROCEDURE Swap(BYREF A : INTEGER, B : INTEGER)
+                DECLARE Temp : INTEGER
+                Temp <- A
+                A <- B
+                B <- Temp
+            ENDPROCEDURE
+            PROCEDURE Bump(BYVAL X : INTEGER, BYREF Y : INTEGER)
+                X <- X + 1
+                Y <- Y + 1
+            ENDPROCEDURE
+            DECLARE P : INTEGER
+            DECLARE Q : INTEGER
+            DECLARE List : ARRAY[1:3] OF INTEGER
+            P <- 1
+            Q <- 2
+            CALL Swap(P, Q)
+            List[1] <- 10
+            List[3] <- 30
+            CALL Swap(List[1], List[3])
+            CALL Bump(P, Q)
+            OUTPUT P, ' ', Q, ' ', List[1], ' ', List[3]
+        ";
+        assert_eq!(run(source, "")?, "2 2 30 10\n");
+
+        let procedure = "PROCEDURE Inc(BYREF N : INTEGER)\nN <- N + 1\nENDPROCEDURE\n";
+        assert!(matches!(run(&format!("{procedure}CALL Inc(1)"), ""), Err(RuntimeError::InvalidTarget)));
+        assert!(matches!(
+            run(&format!("{procedure}DECLARE R : REAL\nR <- 1\nCALL Inc(R)"), ""),
+            Err(RuntimeError::ReferenceMismatch { .. })
+        ));
+        assert!(matches!(
+            run(&format!("{procedure}CONSTANT C <- 1\nCALL Inc(C)"), ""),
+            Err(RuntimeError::AssignToConstant(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn records() -> Result<(), RuntimeError> {
+        let source = "
+            TYPE Student
+                DECLARE Name : STRING
+                DECLARE Marks : ARRAY[1:2] OF INTEGER
+            ENDTYPE
+            PROCEDURE AddMark(BYREF Mark : INTEGER)
+                Mark <- Mark + 5
+            ENDPROCEDURE
+            DECLARE Class : ARRAY[1:2] OF Student
+            DECLARE Best : Student
+            Class[1].Name <- \"Ada\"
+            Class[1].Marks[1] <- 70
+            Class[1].Marks[2] <- 80
+            INPUT Class[2].Name
+            Class[2].Marks[1] <- 90
+            CALL AddMark(Class[2].Marks[1])
+            Best <- Class[2]
+            Best.Marks[2] <- 60
+            OUTPUT Class[1].Name, ' ', Class[1].Marks[2]
+            OUTPUT Best
+            OUTPUT Class[2]
+        ";
+        assert_eq!(run(source, "Alan\n")?, "Ada 80\n(Alan, [95, 60])\n(Alan, [95, _])\n");
+
+        let record = "TYPE R\nDECLARE A : INTEGER\nENDTYPE\nDECLARE X : R\n";
+        assert!(matches!(run(&format!("{record}X.B <- 1"), ""), Err(RuntimeError::UndefinedField(_))));
+        assert!(matches!(run(&format!("{record}X <- 1"), ""), Err(RuntimeError::TypeMismatch { .. })));
+        assert!(matches!(run("DECLARE X : Missing", ""), Err(RuntimeError::UndefinedType(_))));
+        Ok(())
+    }
+
+    #[test]
+    fn enumerated_types() -> Result<(), RuntimeError> {
+        let source = "
+            TYPE Season = (Spring, Summer, Autumn, Winter)
+            DECLARE Today : Season
+            Today <- Autumn
+            OUTPUT Today > Summer, ' ', Today = Winter, ' ', Today
+        ";
+        assert_eq!(run(source, "")?, "TRUE FALSE 2\n");
+
+        let season = "TYPE Season = (Spring, Summer)\n";
+        assert!(matches!(run(&format!("{season}Spring <- Summer"), ""), Err(RuntimeError::AssignToConstant(_))));
+        let mismatch = format!("{season}DECLARE S : Season\nS <- 1");
+        assert!(matches!(run(&mismatch, ""), Err(RuntimeError::TypeMismatch { .. })));
+        Ok(())
+    }
+
+    #[test]
+    fn linked_list() -> Result<(), RuntimeError> {
+        let source = "
+            TYPE NodePtr = ^Node
+            TYPE Node
+                DECLARE Value : INTEGER
+                DECLARE Next : NodePtr
+            ENDTYPE
+            DECLARE Nodes : ARRAY[1:3] OF Node
+            DECLARE Current : NodePtr
+            DECLARE Last : NodePtr
+            FOR I <- 1 TO 3
+                Nodes[I].Value <- I * I
+            NEXT I
+            Nodes[1].Next <- ^Nodes[2]
+            Nodes[2].Next <- ^Nodes[3]
+            Last <- ^Nodes[3]
+            Current <- ^Nodes[1]
+            WHILE Current <> Last DO
+                OUTPUT Current^.Value
+                Current <- Current^.Next
+            ENDWHILE
+            Current^.Value <- 10
+            OUTPUT Nodes[3].Value
+        ";
+        assert_eq!(run(source, "")?, "1\n4\n10\n");
+
+        let pointer = "DECLARE N : INTEGER\nDECLARE P : ^INTEGER\nDECLARE R : REAL\n";
+        assert_eq!(run(&format!("{pointer}P <- ^N\nP^ <- 42\nOUTPUT N"), "")?, "42\n");
+        assert!(matches!(run(&format!("{pointer}P <- ^R"), ""), Err(RuntimeError::TypeMismatch { .. })));
+        assert!(matches!(run(&format!("{pointer}N <- 1\nOUTPUT N^"), ""), Err(RuntimeError::NotAPointer(_))));
+        assert!(matches!(run(&format!("{pointer}OUTPUT P^"), ""), Err(RuntimeError::UnassignedVariable(_))));
+        Ok(())
+    }
+
+    #[test]
+    fn classes() -> Result<(), RuntimeError> {
+        let source = "
+            CLASS Pet
+                PRIVATE Name : STRING
+                PUBLIC PROCEDURE NEW(GivenName : STRING)
+                    Name <- GivenName
+                ENDPROCEDURE
+                PUBLIC FUNCTION GetName() RETURNS STRING
+                    RETURN Name
+                ENDFUNCTION
+                PUBLIC PROCEDURE Describe()
+                    OUTPUT GetName(), \" the pet\"
+                ENDPROCEDURE
+            ENDCLASS
+            CLASS Cat INHERITS Pet
+                PUBLIC Lives : INTEGER
+                PUBLIC PROCEDURE NEW(GivenName : STRING)
+                    CALL SUPER.NEW(GivenName)
+                    Lives <- 9
+                ENDPROCEDURE
+                PUBLIC PROCEDURE Describe()
+                    CALL SUPER.Describe()
+                    OUTPUT \"with \", Lives, \" lives\"
+                ENDPROCEDURE
+            ENDCLASS
+            DECLARE MyPet : Pet
+            DECLARE Other : Pet
+            DECLARE MyCat : Cat
+            MyCat <- NEW Cat(\"Tom\")
+            MyPet <- MyCat
+            Other <- MyPet
+            MyCat.Lives <- MyCat.Lives - 1
+            OUTPUT Other.GetName()
+            CALL MyPet.Describe()
+            OUTPUT MyPet = Other, ' ', MyPet = NEW Pet(\"Tom\")
+        ";
+        assert_eq!(run(source, "")?, "Tom\nTom the pet\nwith 8 lives\nTRUE FALSE\n");
+
+        let class = "
+            CLASS Counter
+                PRIVATE Count : INTEGER
+                PRIVATE PROCEDURE Reset()
+                    Count <- 0
+                ENDPROCEDURE
+            ENDCLASS
+            DECLARE C : Counter
+            C <- NEW Counter
+        ";
+        assert!(matches!(run(&format!("{class}C.Count <- 1"), ""), Err(RuntimeError::PrivateMember(_))));
+        assert!(matches!(run(&format!("{class}CALL C.Reset()"), ""), Err(RuntimeError::PrivateMember(_))));
+        assert!(matches!(run(&format!("{class}C.Total <- 1"), ""), Err(RuntimeError::UndefinedField(_))));
+        assert!(matches!(run(&format!("{class}C <- NEW Counter(1)"), ""), Err(RuntimeError::ArgumentCount { .. })));
+        assert!(matches!(run("DECLARE C : INTEGER\nC <- NEW Missing", ""), Err(RuntimeError::UndefinedClass(_))));
+        Ok(())
+    }
+
+    #[test]
+    fn runtime_errors() {
+        assert!(matches!(run("OUTPUT X", ""), Err(RuntimeError::UndeclaredVariable(_))));
+        assert!(matches!(run("DECLARE X : INTEGER\nOUTPUT X", ""), Err(RuntimeError::UnassignedVariable(_))));
+        assert!(matches!(run("CONSTANT X <- 1\nX <- 2", ""), Err(RuntimeError::AssignToConstant(_))));
+        assert!(matches!(run("OUTPUT 1 / 0", ""), Err(RuntimeError::DivisionByZero)));
+        assert!(matches!(run("OUTPUT 1 + TRUE", ""), Err(RuntimeError::InvalidOperands(..))));
+    }
+}
@@ -0,0 +1,181 @@
+//! Fixed-size binary encoding of values, used for records in random-access files.
+//!
+//! Every primitive is stored as a presence byte followed by its payload, so a record that was
+//! written with unassigned fields reads back with those fields still unassigned. Strings are
+//! padded to `STRING_CAPACITY` bytes so that every record of a given type has the same size.
+//! Pointers and objects refer to data in a running program, so they cannot be stored.
+
+use std::rc::Rc;
+
+use crate::ast::PrimitiveType;
+use crate::interpreter::{Array, Record, RuntimeError, Value, ValueType};
+
+const STRING_CAPACITY: usize = 255;
+
+pub fn encoded_size(type_: &ValueType) -> usize {
+    match type_ {
+        ValueType::Primitive(primitive) => {
+            1 + match primitive {
+                PrimitiveType::Char => 4,
+                PrimitiveType::String => 1 + STRING_CAPACITY,
+                PrimitiveType::Integer | PrimitiveType::Real => 8,
+                PrimitiveType::Boolean => 1,
+            }
+        }
+        ValueType::Enum(_) => 1 + 8,
+        ValueType::Class(_) | ValueType::Pointer(_) | ValueType::Named(_) => 0,
+        ValueType::Array { element_type, bounds } => {
+            let len: usize = bounds.iter().map(|(lower, upper)| (upper - lower + 1) as usize).product();
+            len * encoded_size(element_type)
+        }
+        ValueType::Record(record_type) => record_type.fields.iter().map(|(_, field_type)| encoded_size(field_type)).sum(),
+    }
+}
+
+pub fn encode(value: &Option<Value>, type_: &ValueType, bytes: &mut Vec<u8>) -> Result<(), RuntimeError> {
+    let start = bytes.len();
+    let value = match (value, type_) {
+        (_, ValueType::Class(_) | ValueType::Pointer(_) | ValueType::Named(_)) => {
+            return Err(RuntimeError::NotStorable(type_.clone()))
+        }
+        (None, _) => {
+            bytes.resize(start + encoded_size(type_), 0);
+            return Ok(());
+        }
+        (Some(Value::Array(array)), ValueType::Array { .. }) => {
+            for element in &array.elements {
+                encode(element, &array.element_type, bytes)?;
+            }
+            return Ok(());
+        }
+        (Some(Value::Record(record)), ValueType::Record(_)) => {
+            for (field, (_, field_type)) in record.fields.iter().zip(&record.type_.fields) {
+                encode(field, field_type, bytes)?;
+            }
+            return Ok(());
+        }
+        (Some(value), _) => value.clone().coerce(type_)?,
+    };
+    bytes.push(1);
+    match value {
+        Value::Char(c) => bytes.extend((c as u32).to_le_bytes()),
+        Value::String(s) => {
+            if s.len() > STRING_CAPACITY {
+                return Err(RuntimeError::StringTooLong(s));
+            }
+            bytes.push(s.len() as u8);
+            bytes.extend(s.as_bytes());
+            bytes.resize(start + encoded_size(type_), 0);
+        }
+        Value::Integer(i) => bytes.extend(i.to_le_bytes()),
+        Value::Real(r) => bytes.extend(r.to_le_bytes()),
+        Value::Boolean(b) => bytes.push(b as u8),
+        Value::Enum(_, ordinal) => bytes.extend((ordinal as u64).to_le_bytes()),
+        Value::Array(_) | Value::Record(_) | Value::Pointer(_) | Value::Object(..) => unreachable!("encoded above"),
+    }
+    Ok(())
+}
+
+fn take<'a>(bytes: &mut &'a [u8], len: usize) -> &'a [u8] {
+    let (taken, rest) = bytes.split_at(len);
+    *bytes = rest;
+    taken
+}
+
+/// Decodes a value of `type_` from the front of `bytes`, which must hold at least
+/// `encoded_size(type_)` bytes.
+pub fn decode(bytes: &mut &[u8], type_: &ValueType) -> Result<Option<Value>, RuntimeError> {
+    match type_ {
+        ValueType::Array { element_type, bounds } => {
+            let mut array = Array::new((**element_type).clone(), bounds.clone())?;
+            for element in &mut array.elements {
+                *element = decode(bytes, element_type)?;
+            }
+            return Ok(Some(Value::Array(Box::new(array))));
+        }
+        ValueType::Record(record_type) => {
+            let mut record = Record::new(Rc::clone(record_type))?;
+            for (field, (_, field_type)) in record.fields.iter_mut().zip(&record_type.fields) {
+                *field = decode(bytes, field_type)?;
+            }
+            return Ok(Some(Value::Record(Box::new(record))));
+        }
+        ValueType::Class(_) | ValueType::Pointer(_) | ValueType::Named(_) => {
+            return Err(RuntimeError::NotStorable(type_.clone()))
+        }
+        ValueType::Primitive(_) | ValueType::Enum(_) => {}
+    }
+    let mut payload = take(bytes, encoded_size(type_));
+    if take(&mut payload, 1)[0] == 0 {
+        return Ok(None);
+    }
+    let value = match type_ {
+        ValueType::Primitive(PrimitiveType::Char) => {
+            let code = u32::from_le_bytes(payload.try_into().unwrap());
+            Value::Char(char::from_u32(code).ok_or(RuntimeError::InvalidRecord)?)
+        }
+        ValueType::Primitive(PrimitiveType::String) => {
+            let len = take(&mut payload, 1)[0] as usize;
+            let s = std::str::from_utf8(&payload[..len]).map_err(|_| RuntimeError::InvalidRecord)?;
+            Value::String(s.into())
+        }
+        ValueType::Primitive(PrimitiveType::Integer) => Value::Integer(i64::from_le_bytes(payload.try_into().unwrap())),
+        ValueType::Primitive(PrimitiveType::Real) => Value::Real(f64::from_le_bytes(payload.try_into().unwrap())),
+        ValueType::Primitive(PrimitiveType::Boolean) => Value::Boolean(payload[0] != 0),
+        ValueType::Enum(enum_type) => {
+            let ordinal = u64::from_le_bytes(payload.try_into().unwrap()) as usize;
+            if ordinal >= enum_type.values.len() {
+                return Err(RuntimeError::InvalidRecord);
+            }
+            Value::Enum(Rc::clone(enum_type), ordinal)
+        }
+        _ => unreachable!("decoded above"),
+    };
+    Ok(Some(value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::RecordType;
+
+    #[test]
+    fn round_trip() -> Result<(), RuntimeError> {
+        let type_ = ValueType::Array {
+            element_type: Box::new(ValueType::Primitive(PrimitiveType::String)),
+            bounds: vec![(0, 2)],
+        };
+        let mut array = Array::new(ValueType::Primitive(PrimitiveType::String), vec![(0, 2)])?;
+        array.elements[0] = Some(Value::String("héllo".into()));
+        array.elements[2] = Some(Value::String("".into()));
+        let value = Some(Value::Array(Box::new(array)));
+
+        let mut bytes = Vec::new();
+        encode(&value, &type_, &mut bytes)?;
+        assert_eq!(bytes.len(), encoded_size(&type_));
+        assert_eq!(decode(&mut bytes.as_slice(), &type_)?, value);
+
+        let record_type = Rc::new(RecordType {
+            name: 0,
+            fields: vec![(1, ValueType::Primitive(PrimitiveType::Integer)), (2, type_.clone())],
+        });
+        let mut record = Record::new(Rc::clone(&record_type))?;
+        record.fields[0] = Some(Value::Integer(-7));
+        let value = Some(Value::Record(Box::new(record)));
+        let type_ = ValueType::Record(record_type);
+        let mut bytes = Vec::new();
+        encode(&value, &type_, &mut bytes)?;
+        assert_eq!(bytes.len(), encoded_size(&type_));
+        assert_eq!(decode(&mut bytes.as_slice(), &type_)?, value);
+
+        let char_type = ValueType::Primitive(PrimitiveType::Char);
+        let mut bytes = Vec::new();
+        encode(&Some(Value::Char('✓')), &char_type, &mut bytes)?;
+        assert_eq!(decode(&mut bytes.as_slice(), &char_type)?, Some(Value::Char('✓')));
+
+        let long = Value::String("x".repeat(STRING_CAPACITY + 1).into());
+        let string_type = ValueType::Primitive(PrimitiveType::String);
+        assert!(matches!(encode(&Some(long), &string_type, &mut Vec::new()), Err(RuntimeError::StringTooLong(_))));
+        Ok(())
+    }
+}
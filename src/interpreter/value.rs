@@ -0,0 +1,396 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::rc::Rc;
+
+use crate::ast::{BinaryOperator, Literal, PrimitiveType, UnaryOperator};
+use crate::interpreter::{Place, RuntimeError};
+
+/// The type of a value at runtime, with array bounds already evaluated.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ValueType {
+    Primitive(PrimitiveType),
+    Array {
+        element_type: Box<ValueType>,
+        bounds: Vec<(i64, i64)>,
+    },
+    Record(Rc<RecordType>),
+    Enum(Rc<EnumType>),
+    Class(Rc<ClassType>),
+    Pointer(Box<ValueType>),
+    /// A user-defined type referred to by name, as the target of a pointer type. See `by_name`.
+    Named(usize),
+}
+
+impl ValueType {
+    /// The type as seen through a pointer. Records and enumerated types are referred to by name,
+    /// which lets a record hold pointers to its own type and lets a pointer type be declared
+    /// before the type it points to.
+    pub fn by_name(&self) -> ValueType {
+        match self {
+            ValueType::Record(record_type) => ValueType::Named(record_type.name),
+            ValueType::Enum(enum_type) => ValueType::Named(enum_type.name),
+            ValueType::Class(class_type) => ValueType::Named(class_type.name),
+            other => other.clone(),
+        }
+    }
+}
+
+/// A record type declared with TYPE … ENDTYPE, with fields in declaration order.
+#[derive(Debug, PartialEq)]
+pub struct RecordType {
+    pub name: usize,
+    pub fields: Vec<(usize, ValueType)>,
+}
+
+impl RecordType {
+    pub fn field_index(&self, handle: usize) -> Option<usize> {
+        self.fields.iter().position(|&(field, _)| field == handle)
+    }
+}
+
+/// An enumerated type declared with `TYPE name = (…)`, with its values in order.
+#[derive(Debug, PartialEq)]
+pub struct EnumType {
+    pub name: usize,
+    pub values: Vec<usize>,
+}
+
+/// A class declared with CLASS … ENDCLASS. Its members are kept by the interpreter.
+#[derive(Debug, PartialEq)]
+pub struct ClassType {
+    pub name: usize,
+    pub parent: Option<Rc<ClassType>>,
+}
+
+impl ClassType {
+    /// Whether this class is `other` or inherits from it, directly or indirectly.
+    pub fn inherits(&self, other: &ClassType) -> bool {
+        self == other || self.parent.as_ref().is_some_and(|parent| parent.inherits(other))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Char(char),
+    String(Rc<str>),
+    Integer(i64),
+    Real(f64),
+    Boolean(bool),
+    Array(Box<Array>),
+    Record(Box<Record>),
+    /// A value of an enumerated type, given by its position in the type's list of values
+    Enum(Rc<EnumType>, usize),
+    Pointer(Box<Pointer>),
+    /// A reference to an object, given by its class and its position in the interpreter's heap
+    Object(Rc<ClassType>, usize),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Array {
+    pub element_type: ValueType,
+    pub bounds: Vec<(i64, i64)>,
+    pub elements: Vec<Option<Value>>,
+}
+
+impl Array {
+    pub fn new(element_type: ValueType, bounds: Vec<(i64, i64)>) -> Result<Self, RuntimeError> {
+        let mut len: usize = 1;
+        for &(lower, upper) in &bounds {
+            if lower > upper {
+                return Err(RuntimeError::InvalidBounds(lower, upper));
+            }
+            len = usize::try_from(upper - lower + 1)
+                .ok()
+                .and_then(|size| len.checked_mul(size))
+                .ok_or(RuntimeError::Overflow)?;
+        }
+        Ok(Array {
+            elements: vec![Value::initial(&element_type)?; len],
+            element_type,
+            bounds,
+        })
+    }
+
+    /// Converts a list of indexes into a position in `elements`, checking each against its bounds.
+    pub fn offset(&self, indexes: &[i64]) -> Result<usize, RuntimeError> {
+        if indexes.len() != self.bounds.len() {
+            return Err(RuntimeError::DimensionMismatch {
+                expected: self.bounds.len(),
+                found: indexes.len(),
+            });
+        }
+        let mut offset = 0;
+        for (&index, &(lower, upper)) in indexes.iter().zip(&self.bounds) {
+            if index < lower || index > upper {
+                return Err(RuntimeError::IndexOutOfBounds { index, bounds: (lower, upper) });
+            }
+            offset = offset * (upper - lower + 1) as usize + (index - lower) as usize;
+        }
+        Ok(offset)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Record {
+    pub type_: Rc<RecordType>,
+    pub fields: Vec<Option<Value>>,
+}
+
+impl Record {
+    pub fn new(type_: Rc<RecordType>) -> Result<Self, RuntimeError> {
+        let fields = type_
+            .fields
+            .iter()
+            .map(|(_, field_type)| Value::initial(field_type))
+            .collect::<Result<_, _>>()?;
+        Ok(Record { type_, fields })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pointer {
+    /// The type of the location pointed to, as given by `ValueType::by_name`
+    pub target: ValueType,
+    pub(super) place: Place,
+}
+
+impl Value {
+    /// The value held by a newly declared variable of `type_`. Arrays and records are created
+    /// straight away so that their elements and fields can be assigned individually.
+    pub fn initial(type_: &ValueType) -> Result<Option<Value>, RuntimeError> {
+        Ok(match type_ {
+            ValueType::Primitive(_)
+            | ValueType::Enum(_)
+            | ValueType::Class(_)
+            | ValueType::Pointer(_)
+            | ValueType::Named(_) => None,
+            ValueType::Array { element_type, bounds } => {
+                let array = Array::new((**element_type).clone(), bounds.clone())?;
+                Some(Value::Array(Box::new(array)))
+            }
+            ValueType::Record(record_type) => Some(Value::Record(Box::new(Record::new(Rc::clone(record_type))?))),
+        })
+    }
+
+    pub fn type_(&self) -> ValueType {
+        match self {
+            Value::Char(_) => ValueType::Primitive(PrimitiveType::Char),
+            Value::String(_) => ValueType::Primitive(PrimitiveType::String),
+            Value::Integer(_) => ValueType::Primitive(PrimitiveType::Integer),
+            Value::Real(_) => ValueType::Primitive(PrimitiveType::Real),
+            Value::Boolean(_) => ValueType::Primitive(PrimitiveType::Boolean),
+            Value::Array(array) => ValueType::Array {
+                element_type: Box::new(array.element_type.clone()),
+                bounds: array.bounds.clone(),
+            },
+            Value::Record(record) => ValueType::Record(Rc::clone(&record.type_)),
+            Value::Enum(enum_type, _) => ValueType::Enum(Rc::clone(enum_type)),
+            Value::Pointer(pointer) => ValueType::Pointer(Box::new(pointer.target.clone())),
+            Value::Object(class_type, _) => ValueType::Class(Rc::clone(class_type)),
+        }
+    }
+
+    /// Converts the value to `type_`, allowing INTEGER to be widened to REAL and objects to be
+    /// used as instances of the classes they inherit from.
+    pub fn coerce(self, type_: &ValueType) -> Result<Value, RuntimeError> {
+        match (self, type_) {
+            (Value::Integer(i), ValueType::Primitive(PrimitiveType::Real)) => Ok(Value::Real(i as f64)),
+            (Value::Object(class_type, object), ValueType::Class(expected)) if class_type.inherits(expected) => {
+                Ok(Value::Object(class_type, object))
+            }
+            (value, type_) if value.type_() == *type_ => Ok(value),
+            (value, type_) => Err(RuntimeError::TypeMismatch {
+                expected: type_.clone(),
+                found: value,
+            }),
+        }
+    }
+
+    /// Parses a line of user input as a value of `type_`.
+    pub fn parse(input: &str, type_: PrimitiveType) -> Option<Value> {
+        let trimmed = input.trim();
+        match type_ {
+            PrimitiveType::Char => {
+                let mut chars = input.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => Some(Value::Char(c)),
+                    _ => None,
+                }
+            }
+            PrimitiveType::String => Some(Value::String(input.into())),
+            PrimitiveType::Integer => trimmed.parse().ok().map(Value::Integer),
+            PrimitiveType::Real => trimmed.parse().ok().map(Value::Real),
+            PrimitiveType::Boolean => match trimmed {
+                "TRUE" => Some(Value::Boolean(true)),
+                "FALSE" => Some(Value::Boolean(false)),
+                _ => None,
+            },
+        }
+    }
+
+    pub fn as_boolean(&self) -> Result<bool, RuntimeError> {
+        match self {
+            Value::Boolean(b) => Ok(*b),
+            other => Err(RuntimeError::TypeMismatch {
+                expected: ValueType::Primitive(PrimitiveType::Boolean),
+                found: other.clone(),
+            }),
+        }
+    }
+
+    pub fn as_integer(&self) -> Result<i64, RuntimeError> {
+        match self {
+            Value::Integer(i) => Ok(*i),
+            other => Err(RuntimeError::TypeMismatch {
+                expected: ValueType::Primitive(PrimitiveType::Integer),
+                found: other.clone(),
+            }),
+        }
+    }
+
+    fn as_real(&self) -> Option<f64> {
+        match self {
+            Value::Integer(i) => Some(*i as f64),
+            Value::Real(r) => Some(*r),
+            _ => None,
+        }
+    }
+}
+
+impl From<&Literal> for Value {
+    fn from(literal: &Literal) -> Self {
+        match literal {
+            Literal::Char(c) => Value::Char(*c),
+            Literal::String(s) => Value::String(s.clone()),
+            Literal::Integer(i) => Value::Integer(*i),
+            Literal::Real(r) => Value::Real(*r),
+            Literal::Boolean(b) => Value::Boolean(*b),
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Char(c) => write!(f, "{c}"),
+            Value::String(s) => write!(f, "{s}"),
+            Value::Integer(i) => write!(f, "{i}"),
+            Value::Real(r) => write!(f, "{r:?}"),
+            Value::Boolean(true) => write!(f, "TRUE"),
+            Value::Boolean(false) => write!(f, "FALSE"),
+            Value::Array(array) => {
+                write!(f, "[")?;
+                write_elements(f, &array.elements)?;
+                write!(f, "]")
+            }
+            Value::Record(record) => {
+                write!(f, "(")?;
+                write_elements(f, &record.fields)?;
+                write!(f, ")")
+            }
+            Value::Enum(_, ordinal) => write!(f, "{ordinal}"),
+            Value::Pointer(_) => write!(f, "<pointer>"),
+            Value::Object(..) => write!(f, "<object>"),
+        }
+    }
+}
+
+fn write_elements(f: &mut fmt::Formatter<'_>, elements: &[Option<Value>]) -> fmt::Result {
+    for (i, element) in elements.iter().enumerate() {
+        if i > 0 {
+            write!(f, ", ")?;
+        }
+        match element {
+            Some(value) => write!(f, "{value}")?,
+            None => write!(f, "_")?,
+        }
+    }
+    Ok(())
+}
+
+pub fn binary(operator: BinaryOperator, left: Value, right: Value) -> Result<Value, RuntimeError> {
+    use BinaryOperator as Op;
+
+    let invalid = |left, right| RuntimeError::InvalidOperands(operator, left, right);
+    let result = match (operator, &left, &right) {
+        (Op::LogicAnd, Value::Boolean(l), Value::Boolean(r)) => Value::Boolean(*l && *r),
+        (Op::LogicOr, Value::Boolean(l), Value::Boolean(r)) => Value::Boolean(*l || *r),
+        (Op::Plus | Op::Minus | Op::Star, Value::Integer(l), Value::Integer(r)) => {
+            let result = match operator {
+                Op::Plus => l.checked_add(*r),
+                Op::Minus => l.checked_sub(*r),
+                _ => l.checked_mul(*r),
+            };
+            Value::Integer(result.ok_or(RuntimeError::Overflow)?)
+        }
+        (Op::Div | Op::Mod, Value::Integer(_), Value::Integer(0)) => return Err(RuntimeError::DivisionByZero),
+        (Op::Div | Op::Mod, Value::Integer(l), Value::Integer(r)) => {
+            let result = match operator {
+                Op::Div => l.checked_div(*r),
+                _ => l.checked_rem(*r),
+            };
+            Value::Integer(result.ok_or(RuntimeError::Overflow)?)
+        }
+        (Op::Concat, Value::String(_) | Value::Char(_), Value::String(_) | Value::Char(_)) => {
+            Value::String(format!("{left}{right}").into())
+        }
+        // Like `/`, `^` always gives a REAL, even for INTEGER operands
+        (Op::Plus | Op::Minus | Op::Star | Op::Slash | Op::Power, _, _) => {
+            let (Some(l), Some(r)) = (left.as_real(), right.as_real()) else {
+                return Err(invalid(left, right));
+            };
+            Value::Real(match operator {
+                Op::Plus => l + r,
+                Op::Minus => l - r,
+                Op::Star => l * r,
+                Op::Power => l.powf(r),
+                _ if r == 0.0 => return Err(RuntimeError::DivisionByZero),
+                _ => l / r,
+            })
+        }
+        (Op::Equal | Op::NotEqual | Op::Less | Op::LessEqual | Op::Greater | Op::GreaterEqual, _, _) => {
+            let ordering = match (&left, &right) {
+                (Value::Char(l), Value::Char(r)) => l.partial_cmp(r),
+                (Value::String(l), Value::String(r)) => l.partial_cmp(r),
+                (Value::Boolean(l), Value::Boolean(r)) if matches!(operator, Op::Equal | Op::NotEqual) => {
+                    l.partial_cmp(r)
+                }
+                (Value::Enum(l_type, l), Value::Enum(r_type, r)) if l_type == r_type => l.partial_cmp(r),
+                // Pointers and objects are only equal or unequal, so any other ordering stands in for
+                // "unequal"
+                (Value::Pointer(l), Value::Pointer(r)) if matches!(operator, Op::Equal | Op::NotEqual) => {
+                    Some(if l.place == r.place { Ordering::Equal } else { Ordering::Less })
+                }
+                (Value::Object(_, l), Value::Object(_, r)) if matches!(operator, Op::Equal | Op::NotEqual) => {
+                    Some(if l == r { Ordering::Equal } else { Ordering::Less })
+                }
+                _ => match (left.as_real(), right.as_real()) {
+                    (Some(l), Some(r)) => l.partial_cmp(&r),
+                    _ => None,
+                },
+            };
+            let Some(ordering) = ordering else {
+                return Err(invalid(left, right));
+            };
+            Value::Boolean(match operator {
+                Op::Equal => ordering.is_eq(),
+                Op::NotEqual => ordering.is_ne(),
+                Op::Less => ordering.is_lt(),
+                Op::LessEqual => ordering.is_le(),
+                Op::Greater => ordering.is_gt(),
+                _ => ordering.is_ge(),
+            })
+        }
+        _ => return Err(invalid(left, right)),
+    };
+    Ok(result)
+}
+
+pub fn unary(operator: UnaryOperator, right: Value) -> Result<Value, RuntimeError> {
+    match (operator, right) {
+        (UnaryOperator::LogicNot, right) => Ok(Value::Boolean(!right.as_boolean()?)),
+        (UnaryOperator::Negate, Value::Integer(i)) => Ok(Value::Integer(i.checked_neg().ok_or(RuntimeError::Overflow)?)),
+        (UnaryOperator::Negate, Value::Real(r)) => Ok(Value::Real(-r)),
+        (operator, right) => Err(RuntimeError::InvalidOperand(operator, right)),
+    }
+}
@@ -0,0 +1,6 @@
+pub mod analysis;
+pub mod ast;
+pub mod diagnostics;
+pub mod interpreter;
+pub mod parser;
+pub mod scanner;
@@ -1,22 +1,250 @@
-use std::io;
+use std::env;
+use std::fs;
+use std::io::{self, IsTerminal};
 use std::io::prelude::*;
+use std::process;
+use std::thread;
 
-mod ast;
-mod parser;
-mod scanner;
+use cambridgescript::analysis;
+use cambridgescript::ast::Program;
+use cambridgescript::diagnostics::{self, Diagnostic, Severity};
+use cambridgescript::interpreter::Interpreter;
+use cambridgescript::scanner::{ScannerOptions, Token};
+use cambridgescript::{parser, scanner};
+
+mod repl;
+
+const USAGE: &str = "\
+Usage: cambridgescript [COMMAND] [OPTIONS] [FILE]
+
+Reads the program from FILE, or from standard input if no file is given.
+
+Commands:
+  run      Run the program (the default)
+  check    Check the program for errors without running it
+  tokens   Print the tokens the program is made of
+  ast      Print the syntax tree of the program
+  repl     Start an interactive session, ignoring FILE
+
+Options:
+  --format <text|json>  How to write errors and warnings [default: text]
+  --any-case-keywords   Accept keywords written in any case, such as endif, with a warning
+  --suggest-ascii       Warn about symbols such as ← that have ASCII forms, such as <-
+  -q, --quiet           Only report errors through the exit status
+  -h, --help            Print this message
+
+Exit status:
+  0  Success
+  1  The program has errors
+  2  The program stopped with a runtime error
+  3  The command line was invalid, or FILE could not be read";
+
+const EXIT_DIAGNOSTICS: i32 = 1;
+const EXIT_RUNTIME: i32 = 2;
+const EXIT_USAGE: i32 = 3;
+
+/// The stack given to the thread programs run on, enough for `MAX_CALL_DEPTH` nested calls
+const STACK_SIZE: usize = 256 << 20;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Command {
+    Run,
+    Check,
+    Tokens,
+    Ast,
+    Repl,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Format {
+    Text,
+    Json,
+}
+
+struct Options {
+    command: Command,
+    format: Format,
+    quiet: bool,
+    scanner: ScannerOptions,
+    path: Option<String>,
+}
+
+fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Options, String> {
+    let mut options = Options {
+        command: Command::Run,
+        format: Format::Text,
+        quiet: false,
+        scanner: ScannerOptions::default(),
+        path: None,
+    };
+    let mut args = args.into_iter().peekable();
+    let command = match args.peek().map(String::as_str) {
+        Some("run") => Some(Command::Run),
+        Some("check") => Some(Command::Check),
+        Some("tokens") => Some(Command::Tokens),
+        Some("ast") => Some(Command::Ast),
+        Some("repl") => Some(Command::Repl),
+        _ => None,
+    };
+    if let Some(command) = command {
+        options.command = command;
+        args.next();
+    }
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--format" => {
+                options.format = match args.next().as_deref() {
+                    Some("text") => Format::Text,
+                    Some("json") => Format::Json,
+                    _ => return Err("--format must be followed by text or json".to_string()),
+                }
+            }
+            "-q" | "--quiet" => options.quiet = true,
+            "--any-case-keywords" => options.scanner.case_insensitive_keywords = true,
+            "--suggest-ascii" => options.scanner.suggest_ascii = true,
+            "-h" | "--help" => {
+                println!("{USAGE}");
+                process::exit(0);
+            }
+            // A lone `-` stands for standard input
+            "-" => options.path = None,
+            _ if arg.starts_with('-') => return Err(format!("unknown option {arg}")),
+            _ if options.path.is_some() => return Err(format!("unexpected argument {arg}")),
+            _ => options.path = Some(arg),
+        }
+    }
+    Ok(options)
+}
+
+fn read_source(path: Option<&str>) -> io::Result<String> {
+    match path {
+        Some(path) => fs::read_to_string(path),
+        None => {
+            let mut source = String::new();
+            io::stdin().read_to_string(&mut source)?;
+            Ok(source)
+        }
+    }
+}
+
+fn write_tokens(out: &mut impl Write, tokens: &[Token]) -> io::Result<()> {
+    for token in tokens {
+        let location = token.span.start;
+        writeln!(out, "{}:{} {:?}", location.line, location.column, token.type_)?;
+    }
+    Ok(())
+}
+
+/// Runs the checks on the meaning of a program that parsed without errors.
+fn analyse(program: &mut Program) -> Vec<Diagnostic> {
+    let (symbols, resolve_errors) = analysis::resolve(program);
+    let mut diagnostics: Vec<Diagnostic> = resolve_errors.iter().map(Diagnostic::from).collect();
+    diagnostics.extend(analysis::check_types(program).iter().map(Diagnostic::from));
+    let (flow_errors, flow_warnings) = analysis::check_flow(program);
+    diagnostics.extend(flow_errors.iter().map(Diagnostic::from));
+    diagnostics.extend(flow_warnings.iter().map(Diagnostic::from));
+    diagnostics.extend(analysis::check_assignments(program, &symbols).iter().map(Diagnostic::from));
+    diagnostics
+}
+
+/// Writes `diagnostics` to stderr, leaving stdout to the program.
+fn report(diagnostics: &[Diagnostic], source: &str, path: &str, options: &Options) {
+    if options.quiet {
+        return;
+    }
+    match options.format {
+        Format::Text => {
+            for diagnostic in diagnostics {
+                eprintln!("{}", diagnostics::render(diagnostic, source, path));
+            }
+        }
+        Format::Json => eprintln!("{}", diagnostics::to_json(diagnostics, path)),
+    }
+}
 
 fn main() {
-    let mut input = String::new();
-    let _ = io::stdin().read_to_string(&mut input);
+    // Recursive programs need far more stack than the main thread has to reach the interpreter's
+    // call depth limit
+    let cli = thread::Builder::new().stack_size(STACK_SIZE).spawn(cli).expect("could not start the interpreter");
+    if cli.join().is_err() {
+        process::exit(EXIT_RUNTIME);
+    }
+}
 
-    let (tokens, errors) = scanner::scan(input.as_str());
-    for token in &tokens {
-        dbg!(token);
+fn cli() {
+    let options = parse_args(env::args().skip(1)).unwrap_or_else(|error| {
+        eprintln!("error: {error}\n\n{USAGE}");
+        process::exit(EXIT_USAGE);
+    });
+    if options.command == Command::Repl {
+        let mut repl = repl::Repl::new(io::stdin().lock(), io::stdout(), options.scanner);
+        if io::stdin().is_terminal() {
+            repl = repl.with_line_editor();
+        }
+        if let Err(error) = repl.run() {
+            eprintln!("error: {error}");
+            process::exit(EXIT_USAGE);
+        }
+        return;
     }
-    for error in &errors {
-        dbg!(error);
+    let path = options.path.as_deref().unwrap_or("<stdin>");
+    let source = read_source(options.path.as_deref()).unwrap_or_else(|error| {
+        eprintln!("{path}: {error}");
+        process::exit(EXIT_USAGE);
+    });
+
+    if options.command == Command::Tokens {
+        let (tokens, errors, warnings) = scanner::scan_with_options(&source, options.scanner);
+        // Stop quietly if stdout is closed, as it is when piped into `head`
+        let _ = write_tokens(&mut io::stdout().lock(), &tokens);
+        let mut diagnostics: Vec<Diagnostic> = errors.iter().map(Diagnostic::from).collect();
+        diagnostics.extend(warnings.iter().map(Diagnostic::from));
+        report(&diagnostics, &source, path, &options);
+        process::exit(if errors.is_empty() { 0 } else { EXIT_DIAGNOSTICS });
     }
 
-    let block = parser::parse_block(tokens);
-    dbg!(&block);
+    let (mut program, errors, warnings) = parser::parse_program_with_options(&source, options.scanner);
+    let mut diagnostics: Vec<Diagnostic> = errors.iter().map(Diagnostic::from).collect();
+    diagnostics.extend(warnings.iter().map(Diagnostic::from));
+    // Checks on the meaning of a program rely on it having been parsed in full
+    if errors.is_empty() && options.command != Command::Ast {
+        diagnostics.extend(analyse(&mut program));
+    }
+    let failed = diagnostics.iter().any(|diagnostic| diagnostic.severity == Severity::Error);
+    // JSON for a program that goes on to run is held back, so that any runtime error joins it in a
+    // single document
+    let runs = options.command == Command::Run && !failed;
+    if options.format == Format::Text || !runs {
+        report(&diagnostics, &source, path, &options);
+    }
+    if options.command == Command::Ast {
+        let _ = write!(io::stdout(), "{}", program.body.tree(&program.symbols));
+    }
+    // Warnings are reported, but do not stop the program running
+    if failed {
+        process::exit(EXIT_DIAGNOSTICS);
+    }
+
+    match options.command {
+        Command::Run => {
+            let mut interpreter = Interpreter::new(io::stdin().lock(), io::stdout());
+            let result = interpreter.run(&program.body);
+            if let Err(error) = &result {
+                let span = interpreter.error_span().expect("failed statements are recorded");
+                let diagnostic = Diagnostic::runtime(error, &program.symbols, span);
+                match options.format {
+                    Format::Text => report(&[diagnostic], &source, path, &options),
+                    Format::Json => diagnostics.push(diagnostic),
+                }
+            }
+            if options.format == Format::Json {
+                report(&diagnostics, &source, path, &options);
+            }
+            if result.is_err() {
+                process::exit(EXIT_RUNTIME);
+            }
+        }
+        Command::Check if !options.quiet && options.format == Format::Text => eprintln!("{path}: no errors found"),
+        _ => {}
+    }
 }
@@ -1,12 +1,47 @@
 use crate::ast::*;
-use crate::scanner::{Token, TokenType};
-use std::collections::HashMap;
+use crate::scanner::{self, Location, ScannerError, ScannerOptions, ScannerWarning, Span, Token, TokenType};
+use std::cmp::Ordering;
 use std::rc::Rc;
 
 #[derive(Debug)]
 pub enum ParserError {
     UnexpectedToken(Token),
-    UnexpectedEOF,
+    UnexpectedEOF(Span),
+    MissingCall(Token),
+    DuplicateCaseLabel(Token),
+    OverlappingCaseLabel(Token),
+    /// A block that was not closed by `expected`, such as an IF without an ENDIF
+    UnclosedBlock {
+        opener: Box<Token>,
+        expected: TokenType,
+        span: Span,
+    },
+    /// An error in the source text, found while splitting it into tokens
+    Scanner(ScannerError),
+}
+
+impl ParserError {
+    /// The part of the source code the error refers to.
+    pub fn span(&self) -> Span {
+        match self {
+            ParserError::UnexpectedToken(token)
+            | ParserError::MissingCall(token)
+            | ParserError::DuplicateCaseLabel(token)
+            | ParserError::OverlappingCaseLabel(token) => token.span,
+            ParserError::UnexpectedEOF(span) | ParserError::UnclosedBlock { span, .. } => *span,
+            ParserError::Scanner(error) => error.span(),
+        }
+    }
+
+    /// Whether the input ended before the error was found, so that more input could fix it.
+    pub fn is_incomplete(&self) -> bool {
+        match self {
+            ParserError::UnexpectedEOF(_) => true,
+            // A missing terminator is only reported with an empty span at the end of the input
+            ParserError::UnclosedBlock { span, .. } => span.start == span.end,
+            _ => false,
+        }
+    }
 }
 
 struct TokenBuffer {
@@ -44,7 +79,7 @@ impl TokenBuffer {
     fn consume(&mut self, type_: &TokenType) -> Result<(), ParserError> {
         let next_token = &match self.peek() {
             Some(t) => t,
-            None => return Err(ParserError::UnexpectedEOF),
+            None => return Err(self.eof()),
         };
         if next_token == type_ {
             self.next();
@@ -54,6 +89,18 @@ impl TokenBuffer {
         }
     }
 
+    /// Consumes the terminator of the block started by the token at index `opener`.
+    fn consume_terminator(&mut self, opener: usize, terminator: TokenType) -> Result<(), ParserError> {
+        if self.next_if_equal(&terminator).is_some() {
+            return Ok(());
+        }
+        Err(ParserError::UnclosedBlock {
+            opener: Box::new(self.items[opener].clone()),
+            expected: terminator,
+            span: self.current_token().map_or(self.here(), |token| token.span),
+        })
+    }
+
     fn next_if_equal(&mut self, other: &TokenType) -> Option<TokenType> {
         if &self.peek()? == other {
             self.next()
@@ -62,6 +109,57 @@ impl TokenBuffer {
         }
     }
 
+    /// Looks at the token `distance` places after the next one.
+    fn lookahead(&self, distance: usize) -> Option<&Token> {
+        self.items.get(self.current + distance)
+    }
+
+    /// The tokens from index `start` that lie within `span`, such as the parts of `Pet.Speak`,
+    /// joined into one.
+    fn joined(&self, start: usize, span: Span) -> Token {
+        let parts = self.items[start..].iter().take_while(|token| token.span.end.offset <= span.end.offset);
+        Token {
+            type_: self.items[start].type_.clone(),
+            lexeme: parts.map(|token| &*token.lexeme).collect::<String>().into(),
+            span: Span { start: self.items[start].span.start, end: span.end },
+        }
+    }
+
+    /// The span of the tokens from index `start` up to the current one. It is empty if no
+    /// tokens have been consumed since `start`.
+    fn span_from(&self, start: usize) -> Span {
+        match self.items.get(start) {
+            Some(first) if start < self.current => first.span.to(self.items[self.current - 1].span),
+            _ => self.here(),
+        }
+    }
+
+    /// An empty span where the next token starts.
+    fn here(&self) -> Span {
+        let location = self.location();
+        Span {
+            start: location,
+            end: location,
+        }
+    }
+
+    /// Where the next token starts, or the end of the last token if there are none left.
+    fn location(&self) -> Location {
+        match self.current_token() {
+            Some(token) => token.span.start,
+            None => self.items.last().map_or(Location::new(), |token| token.span.end),
+        }
+    }
+
+    fn eof(&self) -> ParserError {
+        ParserError::UnexpectedEOF(self.here())
+    }
+
+    /// The line of the last token consumed, if any.
+    fn line(&self) -> Option<u32> {
+        self.current.checked_sub(1).map(|previous| self.items[previous].span.end.line)
+    }
+
     fn backtrack(&mut self) {
         if self.current > 0 {
             self.current -= 1;
@@ -84,6 +182,11 @@ macro_rules! binary_op {
             let mut left = self.$parent(tokens)?;
             loop {
                 let op = match tokens.peek() {
+                    // A `-` starting a line begins a negative value, such as a CASE label, rather
+                    // than subtracting from the line before
+                    Some(TokenType::Minus) if tokens.lookahead(0).unwrap().span.start.line != left.span.end.line => {
+                        break
+                    }
                     $(
                         Some(TokenType::$token) => $op,
                     )+
@@ -91,10 +194,14 @@ macro_rules! binary_op {
                 };
                 tokens.next();
                 let right = self.$parent(tokens)?;
-                left = Expr::Binary {
-                    left: Box::new(left),
-                    operator: op,
-                    right: Box::new(right),
+                let span = left.span.to(right.span);
+                left = Expr {
+                    kind: ExprKind::Binary {
+                        left: Box::new(left),
+                        operator: op,
+                        right: Box::new(right),
+                    },
+                    span,
                 }
             };
             Ok(left)
@@ -111,8 +218,7 @@ macro_rules! comma_separated {
                 Some(TokenType::Comma) => {
                     $tokens.next();
                 }
-                Some(_) => break Ok(right),
-                None => break Err(ParserError::UnexpectedEOF),
+                _ => break Ok(right),
             }
         }
     }};
@@ -133,7 +239,7 @@ macro_rules! comma_separated {
                         $tokens.backtrack();
                         break unexpected_token!($tokens);
                     }
-                    None => break Err(ParserError::UnexpectedEOF),
+                    None => break Err($tokens.eof()),
                 }
             }
         }
@@ -141,36 +247,168 @@ macro_rules! comma_separated {
 }
 
 struct Parser {
-    identifier_map: HashMap<Rc<str>, usize>,
+    symbols: SymbolTable,
+    /// Errors that the parser has recovered from
+    errors: Vec<ParserError>,
+    blocks_parsed: usize,
 }
 
 impl Parser {
     fn new() -> Self {
+        let mut symbols = SymbolTable::default();
+        for builtin in Builtin::ALL {
+            symbols.intern(Rc::from(builtin.name()));
+        }
+        symbols.intern(Rc::from("NEW"));
         Parser {
-            identifier_map: HashMap::new(),
+            symbols,
+            errors: Vec::new(),
+            blocks_parsed: 0,
         }
     }
 
+    /// Parses statements up to the end of the enclosing block. A statement with a syntax error
+    /// is recorded and skipped, so that the rest of the block can still be checked.
     fn parse_block(&mut self, tokens: &mut TokenBuffer) -> Block {
         let mut contents = Vec::new();
-        while let Ok(stmt) = self.parse_stmt(tokens) {
-            contents.push(stmt);
+        while let Some(token) = tokens.peek() {
+            if ends_block(&token) {
+                break;
+            }
+            let start = tokens.current;
+            let blocks_parsed = self.blocks_parsed;
+            match self.parse_stmt(tokens) {
+                Ok(stmt) => contents.push(stmt),
+                Err(error) => {
+                    self.errors.push(error);
+                    if tokens.current == start {
+                        tokens.next();
+                    }
+                    self.synchronize(tokens);
+                    // If the error was in the header of a block, the block's body still follows
+                    if self.blocks_parsed == blocks_parsed {
+                        self.skip_body(tokens, start);
+                    }
+                }
+            }
+        }
+        self.blocks_parsed += 1;
+        Block { contents }
+    }
+
+    /// Parses statements up to the end of the input, reporting any tokens left over.
+    fn parse_body(&mut self, tokens: &mut TokenBuffer) -> Block {
+        let mut contents = Vec::new();
+        loop {
+            contents.extend(self.parse_block(tokens).contents);
+            // Only a terminator with no block to close, or a stray CASE label, ends a block here
+            if tokens.current_token().is_none() {
+                break;
+            }
+            self.errors.push(ParserError::UnexpectedToken(tokens.current_token().unwrap().clone()));
+            tokens.next();
+            self.synchronize(tokens);
         }
         Block { contents }
     }
 
+    /// Skips the rest of a statement with an error, up to the next line, statement keyword or
+    /// block terminator.
+    fn synchronize(&mut self, tokens: &mut TokenBuffer) {
+        let line = tokens.line();
+        while let Some(token) = tokens.current_token() {
+            // FOR also appears in OPENFILE, so it only starts a statement on a new line
+            let keyword = starts_statement(&token.type_) && token.type_ != TokenType::For;
+            if Some(token.span.start.line) != line || keyword || is_terminator(&token.type_) {
+                break;
+            }
+            tokens.next();
+        }
+    }
+
+    /// Parses the body of a block statement whose header could not be parsed, up to and
+    /// including its terminator, so that the terminator is not mistaken for a stray one.
+    fn skip_body(&mut self, tokens: &mut TokenBuffer, opener: usize) {
+        let opener = tokens.items[opener].type_.clone();
+        let terminator = match opener {
+            TokenType::If => TokenType::EndIf,
+            TokenType::While => TokenType::EndWhile,
+            TokenType::For => TokenType::Next,
+            TokenType::Repeat => TokenType::Until,
+            TokenType::Procedure => TokenType::EndProcedure,
+            TokenType::Function => TokenType::EndFunction,
+            TokenType::Case => TokenType::EndCase,
+            TokenType::Class => TokenType::EndClass,
+            _ => return,
+        };
+        if matches!(opener, TokenType::Case | TokenType::Class) {
+            // The labels and members of these blocks are not statements, so they are skipped
+            let mut depth = 0;
+            while let Some(token) = tokens.next() {
+                if token == opener {
+                    depth += 1;
+                } else if token == terminator {
+                    if depth == 0 {
+                        break;
+                    }
+                    depth -= 1;
+                }
+            }
+        } else {
+            loop {
+                self.parse_block(tokens);
+                match tokens.peek() {
+                    Some(TokenType::Else) if opener == TokenType::If => {
+                        tokens.next();
+                    }
+                    Some(token) if token == terminator => {
+                        tokens.next();
+                        break;
+                    }
+                    _ => return,
+                }
+            }
+        }
+        // Skip the rest of the line, such as the condition after UNTIL
+        self.synchronize(tokens);
+    }
+
+    /// Consumes the terminator of the block started by the token at index `opener`, or records
+    /// an error if it is missing.
+    fn close_block(&mut self, tokens: &mut TokenBuffer, opener: usize, terminator: TokenType) {
+        if let Err(error) = tokens.consume_terminator(opener, terminator) {
+            // Running out of input inside the block has already been reported
+            let reported = tokens.peek().is_none()
+                && matches!(self.errors.last(), Some(ParserError::UnexpectedEOF(_)));
+            if !reported {
+                self.errors.push(error);
+            }
+        }
+    }
+
     fn parse_stmt(&mut self, tokens: &mut TokenBuffer) -> Result<Stmt, ParserError> {
+        let start = tokens.current;
+        let kind = self.parse_stmt_kind(tokens)?;
+        Ok(Stmt {
+            kind,
+            span: tokens.span_from(start),
+        })
+    }
+
+    fn parse_stmt_kind(&mut self, tokens: &mut TokenBuffer) -> Result<StmtKind, ParserError> {
+        let opener = tokens.current;
         let next_token = match tokens.next() {
             Some(t) => t,
-            None => return Err(ParserError::UnexpectedEOF),
+            None => return Err(tokens.eof()),
         };
         let res = match next_token {
             TokenType::Procedure => {
-                let name = self.parse_identifier(tokens)?;
+                // Constructors are procedures called NEW
+                let name = self.parse_member_name(tokens)?;
                 let params = self.parse_parameter_list(tokens)?;
                 let body = self.parse_block(tokens);
-                tokens.consume(&TokenType::EndProcedure)?;
-                Stmt::ProcedureDecl {
+                self.close_block(tokens, opener, TokenType::EndProcedure);
+                StmtKind::ProcedureDecl {
                     name,
                     params,
                     body,
@@ -182,8 +420,8 @@ impl Parser {
                 tokens.consume(&TokenType::Returns)?;
                 let return_type = self.parse_type(tokens)?;
                 let body = self.parse_block(tokens);
-                tokens.consume(&TokenType::EndFunction)?;
-                Stmt::FunctionDecl {
+                self.close_block(tokens, opener, TokenType::EndFunction);
+                StmtKind::FunctionDecl {
                     name,
                     params,
                     return_type,
@@ -198,15 +436,51 @@ impl Parser {
                     Ok(_) => Some(self.parse_block(tokens)),
                     Err(_) => None
                 };
-                tokens.consume(&TokenType::EndIf)?;
-                Stmt::If {
+                self.close_block(tokens, opener, TokenType::EndIf);
+                StmtKind::If {
                     condition,
                     then_branch,
                     else_branch,
                 }
             },
-            TokenType::Return => Stmt::Return(self.parse_expression(tokens)?),
-            TokenType::Case => unimplemented!(),
+            TokenType::Return => StmtKind::Return(self.parse_expression(tokens)?),
+            TokenType::Case => {
+                tokens.consume(&TokenType::Of)?;
+                let condition = self.parse_expression(tokens)?;
+                let mut cases: Vec<(CaseLabel, Block)> = Vec::new();
+                let mut otherwise = None;
+                loop {
+                    match tokens.peek() {
+                        Some(TokenType::EndCase) | None => break,
+                        Some(TokenType::Otherwise) => {
+                            tokens.next();
+                            tokens.next_if_equal(&TokenType::Colon);
+                            otherwise = Some(self.parse_block(tokens));
+                            break;
+                        }
+                        // A terminator of an enclosing block, left for it to close
+                        Some(token) if is_terminator(&token) => break,
+                        Some(_) => {
+                            let label_token = tokens.current_token().unwrap().clone();
+                            let label = self.parse_case_label(tokens)?;
+                            for (other, _) in &cases {
+                                if let Some(error) = case_label_conflict(&label, other, &label_token) {
+                                    self.errors.push(error);
+                                }
+                            }
+                            tokens.consume(&TokenType::Colon)?;
+                            let body = self.parse_block(tokens);
+                            cases.push((label, body));
+                        }
+                    }
+                }
+                self.close_block(tokens, opener, TokenType::EndCase);
+                StmtKind::CaseOf {
+                    condition,
+                    cases,
+                    otherwise,
+                }
+            }
             TokenType::For => {
                 let target = self.parse_assignable(tokens)?;
                 tokens.consume(&TokenType::LArrow)?;
@@ -218,8 +492,14 @@ impl Parser {
                     Err(_) => None,
                 };
                 let body = self.parse_block(tokens);
-                tokens.consume(&TokenType::Next)?;
-                Stmt::ForLoop {
+                self.close_block(tokens, opener, TokenType::Next);
+                // The loop counter may optionally be repeated after NEXT
+                if let (Some(TokenType::Identifier(ident)), ExprKind::Identifier { handle, .. }) = (tokens.peek(), &target.kind) {
+                    if self.get_ident_handle(ident) == *handle {
+                        tokens.next();
+                    }
+                }
+                StmtKind::ForLoop {
                     target,
                     start,
                     end,
@@ -229,49 +509,143 @@ impl Parser {
             },
             TokenType::Repeat => {
                 let body = self.parse_block(tokens);
-                tokens.consume(&TokenType::Until)?;
+                tokens.consume_terminator(opener, TokenType::Until)?;
                 let condition = self.parse_expression(tokens)?;
-                Stmt::RepeatUntil { condition, body }
+                StmtKind::RepeatUntil { condition, body }
             }
             TokenType::While => {
                 let condition = self.parse_expression(tokens)?;
                 tokens.consume(&TokenType::Do)?;
                 let body = self.parse_block(tokens);
-                tokens.consume(&TokenType::EndWhile)?;
-                Stmt::While { condition, body }
+                self.close_block(tokens, opener, TokenType::EndWhile);
+                StmtKind::While { condition, body }
             }
             TokenType::Declare => {
                 let name = self.parse_identifier(tokens)?;
                 tokens.consume(&TokenType::Colon)?;
                 let type_ = self.parse_type(tokens)?;
-                Stmt::VariableDecl { name, type_ }
+                StmtKind::VariableDecl { name, type_ }
+            }
+            TokenType::Type => {
+                let name = self.parse_identifier(tokens)?;
+                if tokens.next_if_equal(&TokenType::Equal).is_some() {
+                    let type_ = match tokens.next_if_equal(&TokenType::LParen) {
+                        Some(_) => Type::Enumerated(comma_separated!(self.parse_identifier(tokens), tokens; RParen)?),
+                        None => self.parse_type(tokens)?,
+                    };
+                    return Ok(StmtKind::TypeDecl { name, type_ });
+                }
+                let mut fields = Vec::new();
+                while tokens.next_if_equal(&TokenType::Declare).is_some() {
+                    fields.push(self.parse_field(tokens)?);
+                }
+                self.close_block(tokens, opener, TokenType::EndType);
+                StmtKind::RecordDecl { name, fields }
+            }
+            TokenType::Class => {
+                let name = self.parse_identifier(tokens)?;
+                let parent = match tokens.next_if_equal(&TokenType::Inherits) {
+                    Some(_) => Some(self.parse_identifier(tokens)?),
+                    None => None,
+                };
+                let mut members = Vec::new();
+                while let Some(member) = self.parse_member(tokens)? {
+                    members.push(member);
+                }
+                self.close_block(tokens, opener, TokenType::EndClass);
+                StmtKind::ClassDecl { name, parent, members }
             }
             TokenType::Constant => {
                 let name = self.parse_identifier(tokens)?;
                 tokens.consume(&TokenType::LArrow)?;
-                let value = match self.parse_primary(tokens)? {
-                    Expr::Literal(l) => l,
-                    _ => return unexpected_token!(tokens),
-                };
-                Stmt::ConstantDecl { name, value }
+                let value = self.parse_literal(tokens)?;
+                StmtKind::ConstantDecl { name, value }
             },
             TokenType::Input => {
-                Stmt::Input(comma_separated!(self.parse_expression(tokens), tokens)?)
+                StmtKind::Input(comma_separated!(self.parse_expression(tokens), tokens)?)
             }
             TokenType::Output => {
-                Stmt::Output(comma_separated!(self.parse_expression(tokens), tokens)?)
+                StmtKind::Output(comma_separated!(self.parse_expression(tokens), tokens)?)
+            }
+            TokenType::Call => match self.parse_assignable(tokens)? {
+                Expr { kind: ExprKind::FunctionCall { function, args }, .. } => StmtKind::Procedure {
+                    name: *function,
+                    args: Some(args),
+                },
+                name => StmtKind::Procedure { name, args: None },
+            },
+            TokenType::OpenFile => {
+                let file = self.parse_expression(tokens)?;
+                tokens.consume(&TokenType::For)?;
+                let mode = match tokens.next() {
+                    Some(TokenType::Read) => FileMode::Read,
+                    Some(TokenType::Write) => FileMode::Write,
+                    Some(TokenType::Append) => FileMode::Append,
+                    Some(TokenType::Random) => FileMode::Random,
+                    Some(_) => {
+                        tokens.backtrack();
+                        return unexpected_token!(tokens);
+                    }
+                    None => return Err(tokens.eof()),
+                };
+                StmtKind::FileOpen { file, mode }
+            }
+            TokenType::ReadFile => {
+                let file = self.parse_expression(tokens)?;
+                tokens.consume(&TokenType::Comma)?;
+                let target = self.parse_assignable(tokens)?;
+                StmtKind::FileRead { file, target }
+            }
+            TokenType::WriteFile => {
+                let file = self.parse_expression(tokens)?;
+                tokens.consume(&TokenType::Comma)?;
+                let value = self.parse_expression(tokens)?;
+                StmtKind::FileWrite { file, value }
+            }
+            TokenType::CloseFile => StmtKind::FileClose {
+                file: self.parse_expression(tokens)?,
+            },
+            TokenType::Seek => {
+                let file = self.parse_expression(tokens)?;
+                tokens.consume(&TokenType::Comma)?;
+                let address = self.parse_expression(tokens)?;
+                StmtKind::FileSeek { file, address }
+            }
+            TokenType::GetRecord => {
+                let file = self.parse_expression(tokens)?;
+                tokens.consume(&TokenType::Comma)?;
+                let target = self.parse_assignable(tokens)?;
+                StmtKind::FileGetRecord { file, target }
+            }
+            TokenType::PutRecord => {
+                let file = self.parse_expression(tokens)?;
+                tokens.consume(&TokenType::Comma)?;
+                let source = self.parse_assignable(tokens)?;
+                StmtKind::FilePutRecord { file, source }
             }
-            TokenType::Call => unimplemented!(),
-            TokenType::OpenFile => unimplemented!(),
-            TokenType::ReadFile => unimplemented!(),
-            TokenType::WriteFile => unimplemented!(),
-            TokenType::CloseFile => unimplemented!(),
             _ => {
                 tokens.backtrack();
+                let start = tokens.current;
                 let target = self.parse_assignable(tokens)?;
+                let missing_call = match &target.kind {
+                    // A procedure or method call written without CALL
+                    ExprKind::FunctionCall { function, .. } if tokens.peek() != Some(TokenType::LArrow) => {
+                        Some(function.span)
+                    }
+                    // A procedure name alone on its line, with neither CALL nor parentheses
+                    ExprKind::Identifier { .. } | ExprKind::FieldAccess { .. }
+                        if tokens.current_token().is_none_or(|next| next.span.start.line > target.span.end.line) =>
+                    {
+                        Some(target.span)
+                    }
+                    _ => None,
+                };
+                if let Some(span) = missing_call {
+                    return Err(ParserError::MissingCall(tokens.joined(start, span)));
+                }
                 tokens.consume(&TokenType::LArrow)?;
                 let value = self.parse_expression(tokens)?;
-                Stmt::Assignment { target, value }
+                StmtKind::Assignment { target, value }
             }
         };
         Ok(res)
@@ -284,32 +658,117 @@ impl Parser {
             Some(TokenType::String) => Ok(Type::Primitive(PrimitiveType::String)),
             Some(TokenType::Char) => Ok(Type::Primitive(PrimitiveType::Char)),
             Some(TokenType::Boolean) => Ok(Type::Primitive(PrimitiveType::Boolean)),
-            Some(TokenType::Array) => { unimplemented!() }
+            Some(TokenType::Array) => {
+                tokens.consume(&TokenType::LBracket)?;
+                let ranges = comma_separated!(self.parse_array_range(tokens), tokens)?;
+                tokens.consume(&TokenType::RBracket)?;
+                tokens.consume(&TokenType::Of)?;
+                let start = tokens.current;
+                let inner_type = match self.parse_type(tokens)? {
+                    Type::Array(_) => {
+                        tokens.current = start;
+                        return unexpected_token!(tokens);
+                    }
+                    inner_type => Box::new(inner_type),
+                };
+                Ok(Type::Array(ArrayType { inner_type, ranges }))
+            }
+            Some(TokenType::Identifier(_)) => {
+                tokens.backtrack();
+                Ok(Type::Named(self.parse_identifier(tokens)?))
+            }
+            Some(TokenType::Caret) => Ok(Type::Pointer(Box::new(self.parse_type(tokens)?))),
             Some(_) => {
                 tokens.backtrack();
                 unexpected_token!(tokens)
             }
-            None => Err(ParserError::UnexpectedEOF),
+            None => Err(tokens.eof()),
+        }
+    }
+
+    fn parse_array_range(&mut self, tokens: &mut TokenBuffer) -> Result<(Expr, Expr), ParserError> {
+        let lower = self.parse_expression(tokens)?;
+        tokens.consume(&TokenType::Colon)?;
+        let upper = self.parse_expression(tokens)?;
+        Ok((lower, upper))
+    }
+
+    fn parse_case_label(&mut self, tokens: &mut TokenBuffer) -> Result<CaseLabel, ParserError> {
+        let start = self.parse_literal(tokens)?;
+        if tokens.next_if_equal(&TokenType::To).is_some() {
+            let end = self.parse_literal(tokens)?;
+            Ok(CaseLabel::Range(start, end))
+        } else {
+            Ok(CaseLabel::Value(start))
         }
     }
 
+    /// Parses an attribute or method declaration inside a class, or returns `None` at the end
+    /// of the class. Members are public unless declared PRIVATE.
+    fn parse_member(&mut self, tokens: &mut TokenBuffer) -> Result<Option<Member>, ParserError> {
+        let explicit_visibility = match tokens.peek() {
+            Some(TokenType::Public) => Some(Visibility::Public),
+            Some(TokenType::Private) => Some(Visibility::Private),
+            _ => None,
+        };
+        if explicit_visibility.is_some() {
+            tokens.next();
+        }
+        let visibility = explicit_visibility.unwrap_or(Visibility::Public);
+        let member = match tokens.peek() {
+            Some(TokenType::Procedure | TokenType::Function) => Member::Method {
+                visibility,
+                decl: self.parse_stmt(tokens)?,
+            },
+            Some(TokenType::Declare) => {
+                tokens.next();
+                Member::Attribute {
+                    visibility,
+                    field: self.parse_field(tokens)?,
+                }
+            }
+            // DECLARE may be left out after PUBLIC or PRIVATE
+            Some(TokenType::Identifier(_)) if explicit_visibility.is_some() => Member::Attribute {
+                visibility,
+                field: self.parse_field(tokens)?,
+            },
+            Some(_) if explicit_visibility.is_some() => return unexpected_token!(tokens),
+            None if explicit_visibility.is_some() => return Err(tokens.eof()),
+            _ => return Ok(None),
+        };
+        Ok(Some(member))
+    }
+
+    fn parse_field(&mut self, tokens: &mut TokenBuffer) -> Result<Field, ParserError> {
+        let name = self.parse_identifier(tokens)?;
+        tokens.consume(&TokenType::Colon)?;
+        let type_ = self.parse_type(tokens)?;
+        Ok(Field { name, type_ })
+    }
+
     fn parse_expression(&mut self, tokens: &mut TokenBuffer) -> Result<Expr, ParserError> {
         self.parse_logic_or(tokens)
     }
 
-    fn parse_parameter(&mut self, tokens: &mut TokenBuffer) -> Result<Parameter, ParserError> {
+    /// Parses a parameter, updating `mode` if a new passing mode is given. Parameters without
+    /// an explicit mode use the mode of the one before them.
+    fn parse_parameter(&mut self, tokens: &mut TokenBuffer, mode: &mut PassingMode) -> Result<Parameter, ParserError> {
+        if tokens.next_if_equal(&TokenType::ByRef).is_some() {
+            *mode = PassingMode::ByReference;
+        } else if tokens.next_if_equal(&TokenType::ByVal).is_some() {
+            *mode = PassingMode::ByValue;
+        }
         let name = self.parse_identifier(tokens)?;
         tokens.consume(&TokenType::Colon)?;
         let type_ = self.parse_type(tokens)?;
-        Ok(Parameter { name, type_ })
+        Ok(Parameter { name, type_, mode: *mode })
     }
 
     fn parse_parameter_list(&mut self, tokens: &mut TokenBuffer) -> Result<Option<Vec<Parameter>>, ParserError> {
         Ok(match tokens.next_if_equal(&TokenType::LParen) {
             Some(_) => {
-                let params = comma_separated!(self.parse_parameter(tokens), tokens)?;
-                tokens.consume(&TokenType::RParen)?;
-                Some(params)
+                let mut mode = PassingMode::ByValue;
+                Some(comma_separated!(self.parse_parameter(tokens, &mut mode), tokens; RParen)?)
             },
             None => None,
         })
@@ -328,10 +787,15 @@ impl Parser {
     }
 
     fn parse_logic_not(&mut self, tokens: &mut TokenBuffer) -> Result<Expr, ParserError> {
+        let start = tokens.current;
         if tokens.consume(&TokenType::Not).is_ok() {
-            Ok(Expr::Unary {
-                operator: UnaryOperator::LogicNot,
-                right: Box::new(self.parse_logic_not(tokens)?),
+            let right = Box::new(self.parse_logic_not(tokens)?);
+            Ok(Expr {
+                kind: ExprKind::Unary {
+                    operator: UnaryOperator::LogicNot,
+                    right,
+                },
+                span: tokens.span_from(start),
             })
         } else {
             self.parse_comparison(tokens)
@@ -339,7 +803,7 @@ impl Parser {
     }
 
     binary_op! {
-        parse_comparison: parse_term {
+        parse_comparison: parse_concatenation {
             Equal => BinaryOperator::Equal,
             NotEqual => BinaryOperator::NotEqual,
             Less => BinaryOperator::Less,
@@ -349,6 +813,10 @@ impl Parser {
         }
     }
 
+    binary_op! {
+        parse_concatenation: parse_term {Ampersand => BinaryOperator::Concat}
+    }
+
     binary_op! {
         parse_term: parse_factor {
             Plus => BinaryOperator::Plus,
@@ -357,98 +825,764 @@ impl Parser {
     }
 
     binary_op! {
-        parse_factor: parse_call {
+        parse_factor: parse_unary {
             Star => BinaryOperator::Star,
             Slash => BinaryOperator::Slash,
+            Div => BinaryOperator::Div,
+            Mod => BinaryOperator::Mod,
+        }
+    }
+
+    fn parse_unary(&mut self, tokens: &mut TokenBuffer) -> Result<Expr, ParserError> {
+        let start = tokens.current;
+        if tokens.next_if_equal(&TokenType::Minus).is_some() {
+            let right = Box::new(self.parse_unary(tokens)?);
+            Ok(Expr {
+                kind: ExprKind::Unary {
+                    operator: UnaryOperator::Negate,
+                    right,
+                },
+                span: tokens.span_from(start),
+            })
+        } else {
+            self.parse_exponent(tokens)
         }
     }
 
+    /// Exponentiation is right-associative, so `2 ^ 3 ^ 2` is `2 ^ (3 ^ 2)`.
+    fn parse_exponent(&mut self, tokens: &mut TokenBuffer) -> Result<Expr, ParserError> {
+        let left = self.parse_call(tokens)?;
+        if tokens.next_if_equal(&TokenType::Caret).is_none() {
+            return Ok(left);
+        }
+        // NOT binds more loosely than `^`, but may start the exponent so that the type checker
+        // can report it
+        let right = if tokens.peek() == Some(TokenType::Not) {
+            self.parse_logic_not(tokens)?
+        } else {
+            self.parse_unary(tokens)?
+        };
+        let span = left.span.to(right.span);
+        Ok(Expr {
+            kind: ExprKind::Binary {
+                left: Box::new(left),
+                operator: BinaryOperator::Power,
+                right: Box::new(right),
+            },
+            span,
+        })
+    }
+
     fn parse_call(&mut self, tokens: &mut TokenBuffer) -> Result<Expr, ParserError> {
+        let start = tokens.current;
         let mut left = self.parse_primary(tokens)?;
         loop {
-            left = match tokens.next() {
+            let kind = match tokens.peek() {
                 Some(TokenType::LParen) => {
+                    tokens.next();
                     let right = comma_separated!(self.parse_expression(tokens), tokens; RParen)?;
-                    Expr::FunctionCall {
+                    ExprKind::FunctionCall {
                         function: Box::new(left),
                         args: right,
                     }
                 }
                 Some(TokenType::LBracket) => {
+                    tokens.next();
                     let right = comma_separated!(self.parse_expression(tokens), tokens; RBracket)?;
-                    Expr::ArrayIndex {
+                    ExprKind::ArrayIndex {
                         array: Box::new(left),
                         indexes: right,
                     }
                 }
-                _ => {
-                    tokens.backtrack();
-                    break;
+                Some(TokenType::Dot) => {
+                    tokens.next();
+                    let field = self.parse_member_name(tokens)?;
+                    ExprKind::FieldAccess {
+                        object: Box::new(left),
+                        field: Box::new(field),
+                    }
+                }
+                // A `^` followed by an operand is exponentiation rather than a dereference
+                Some(TokenType::Caret) if !starts_exponent(tokens, left.span) => {
+                    tokens.next();
+                    ExprKind::Dereference { pointer: Box::new(left) }
                 }
+                _ => break,
+            };
+            left = Expr {
+                kind,
+                span: tokens.span_from(start),
             };
         }
         Ok(left)
     }
 
     fn parse_primary(&mut self, tokens: &mut TokenBuffer) -> Result<Expr, ParserError> {
+        let start = tokens.current;
         let next_token = match tokens.next() {
             Some(t) => t,
-            None => return Err(ParserError::UnexpectedEOF),
+            None => return Err(tokens.eof()),
         };
-        let expr = match next_token {
-            TokenType::Identifier(ident) => Expr::Identifier {
+        let kind = match next_token {
+            TokenType::Identifier(ident) => ExprKind::Identifier {
                 handle: self.get_ident_handle(ident),
+                symbol: None,
             },
-            TokenType::CharLiteral(c) => Expr::Literal(Literal::Char(c)),
-            TokenType::StringLiteral(s) => Expr::Literal(Literal::String(s)),
-            TokenType::IntegerLiteral(i) => Expr::Literal(Literal::Integer(i)),
-            TokenType::RealLiteral(r) => Expr::Literal(Literal::Real(r)),
-            TokenType::BooleanLiteral(b) => Expr::Literal(Literal::Boolean(b)),
+            TokenType::CharLiteral(c) => ExprKind::Literal(Literal::Char(c)),
+            TokenType::StringLiteral(s) => ExprKind::Literal(Literal::String(s)),
+            TokenType::IntegerLiteral(i) => ExprKind::Literal(Literal::Integer(i)),
+            TokenType::RealLiteral(r) => ExprKind::Literal(Literal::Real(r)),
+            TokenType::BooleanLiteral(b) => ExprKind::Literal(Literal::Boolean(b)),
             TokenType::LParen => {
                 let inner = self.parse_expression(tokens)?;
                 tokens.consume(&TokenType::RParen)?;
-                inner
+                return Ok(inner);
+            }
+            TokenType::Caret => ExprKind::AddressOf {
+                target: Box::new(self.parse_call(tokens)?),
+            },
+            TokenType::New => {
+                let class = self.parse_identifier(tokens)?;
+                let args = match tokens.next_if_equal(&TokenType::LParen) {
+                    Some(_) => comma_separated!(self.parse_expression(tokens), tokens; RParen)?,
+                    None => Vec::new(),
+                };
+                ExprKind::New {
+                    class: Box::new(class),
+                    args,
+                }
             }
+            TokenType::Super => ExprKind::Super,
             _ => {
                 tokens.backtrack();
                 return unexpected_token!(tokens);
             }
         };
-        Ok(expr)
+        Ok(Expr {
+            kind,
+            span: tokens.span_from(start),
+        })
+    }
+
+    fn parse_literal(&mut self, tokens: &mut TokenBuffer) -> Result<Literal, ParserError> {
+        let start = tokens.current;
+        // The sign of a negative number may be separated from it
+        let negative = tokens.next_if_equal(&TokenType::Minus).is_some();
+        match (self.parse_primary(tokens)?.kind, negative) {
+            (ExprKind::Literal(l), false) => Ok(l),
+            (ExprKind::Literal(Literal::Integer(i)), true) => Ok(Literal::Integer(-i)),
+            (ExprKind::Literal(Literal::Real(r)), true) => Ok(Literal::Real(-r)),
+            _ => {
+                tokens.current = start;
+                unexpected_token!(tokens)
+            }
+        }
     }
 
     fn parse_identifier(&mut self, tokens: &mut TokenBuffer) -> Result<Expr, ParserError> {
         let expr = self.parse_primary(tokens)?;
-        match expr {
-            Expr::Identifier { .. } => {Ok(expr)},
+        match expr.kind {
+            ExprKind::Identifier { .. } => {Ok(expr)},
             _ => unexpected_token!(tokens),
         }
     }
 
+    /// Parses the name of a field or method, which may be NEW for a constructor.
+    fn parse_member_name(&mut self, tokens: &mut TokenBuffer) -> Result<Expr, ParserError> {
+        let start = tokens.current;
+        match tokens.next_if_equal(&TokenType::New) {
+            Some(_) => Ok(Expr {
+                kind: ExprKind::Identifier {
+                    handle: CONSTRUCTOR,
+                    symbol: None,
+                },
+                span: tokens.span_from(start),
+            }),
+            None => self.parse_identifier(tokens),
+        }
+    }
+
     fn get_ident_handle(&mut self, ident: Rc<str>) -> usize {
-        if let Some(&handle) = self.identifier_map.get(&ident) {
-            return handle;
+        self.symbols.intern(ident)
+    }
+}
+
+/// Whether `token` can only begin a statement.
+fn starts_statement(token: &TokenType) -> bool {
+    matches!(
+        token,
+        TokenType::Procedure
+            | TokenType::Function
+            | TokenType::If
+            | TokenType::Case
+            | TokenType::For
+            | TokenType::Repeat
+            | TokenType::While
+            | TokenType::Declare
+            | TokenType::Type
+            | TokenType::Class
+            | TokenType::Constant
+            | TokenType::Input
+            | TokenType::Output
+            | TokenType::Return
+            | TokenType::Call
+            | TokenType::OpenFile
+            | TokenType::ReadFile
+            | TokenType::WriteFile
+            | TokenType::CloseFile
+            | TokenType::Seek
+            | TokenType::GetRecord
+            | TokenType::PutRecord
+    )
+}
+
+/// Whether `token` ends a block, or separates two blocks of the same statement.
+fn is_terminator(token: &TokenType) -> bool {
+    matches!(
+        token,
+        TokenType::Else
+            | TokenType::EndIf
+            | TokenType::Otherwise
+            | TokenType::EndCase
+            | TokenType::Next
+            | TokenType::Until
+            | TokenType::EndWhile
+            | TokenType::EndProcedure
+            | TokenType::EndFunction
+            | TokenType::EndType
+            | TokenType::EndClass
+    )
+}
+
+/// Whether `token` ends a block of statements. The blocks of a CASE statement are ended by
+/// the label of the next case, which cannot start a statement.
+fn ends_block(token: &TokenType) -> bool {
+    is_terminator(token)
+        || matches!(
+            token,
+            TokenType::CharLiteral(_)
+                | TokenType::StringLiteral(_)
+                | TokenType::IntegerLiteral(_)
+                | TokenType::RealLiteral(_)
+                | TokenType::BooleanLiteral(_)
+                | TokenType::Minus
+        )
+}
+
+fn starts_operand(token: &TokenType) -> bool {
+    matches!(
+        token,
+        TokenType::Identifier(_)
+            | TokenType::CharLiteral(_)
+            | TokenType::StringLiteral(_)
+            | TokenType::IntegerLiteral(_)
+            | TokenType::RealLiteral(_)
+            | TokenType::BooleanLiteral(_)
+            | TokenType::LParen
+            | TokenType::New
+            | TokenType::Super
+    )
+}
+
+/// Whether the current `^`, written after `left`, raises to a power. It does when an operand
+/// follows on the same line, so a `P^` ending a line stays a dereference. A `-` only starts the
+/// exponent when written against its operand and not after a caret hugging `left`: `X ^ -Y` and
+/// `X^-Y` are powers, while `P^ - Y` and `P^ -1` subtract from the value `P` points to.
+fn starts_exponent(tokens: &TokenBuffer, left: Span) -> bool {
+    let Some(caret) = tokens.lookahead(0) else { return false };
+    let same_line = |token: &Token| token.span.start.line == caret.span.end.line;
+    match tokens.lookahead(1) {
+        Some(minus @ Token { type_: TokenType::Minus, .. }) => {
+            let postfix =
+                left.end.offset == caret.span.start.offset && caret.span.end.offset != minus.span.start.offset;
+            !postfix
+                && same_line(minus)
+                && tokens.lookahead(2).is_some_and(|operand| {
+                    operand.span.start.offset == minus.span.end.offset && starts_operand(&operand.type_)
+                })
         }
-        let new_handle = self.identifier_map.len();
-        let _ = self.identifier_map.insert(ident, new_handle);
-        new_handle
+        Some(token) => same_line(token) && (token.type_ == TokenType::Not || starts_operand(&token.type_)),
+        None => false,
     }
 }
 
-pub fn parse_expression(tokens: impl IntoIterator<Item = Token>) -> Result<Expr, ParserError> {
-    let mut buf = TokenBuffer::from_iter(tokens);
-    let mut parser = Parser::new();
-    parser.parse_expression(&mut buf)
+fn compare_literals(left: &Literal, right: &Literal) -> Option<Ordering> {
+    match (left, right) {
+        (Literal::Char(l), Literal::Char(r)) => l.partial_cmp(r),
+        (Literal::String(l), Literal::String(r)) => l.partial_cmp(r),
+        (Literal::Integer(l), Literal::Integer(r)) => l.partial_cmp(r),
+        (Literal::Integer(l), Literal::Real(r)) => (*l as f64).partial_cmp(r),
+        (Literal::Real(l), Literal::Integer(r)) => l.partial_cmp(&(*r as f64)),
+        (Literal::Real(l), Literal::Real(r)) => l.partial_cmp(r),
+        (Literal::Boolean(l), Literal::Boolean(r)) => l.partial_cmp(r),
+        _ => None,
+    }
 }
 
-pub fn parse_statement(tokens: impl IntoIterator<Item = Token>) -> Result<Stmt, ParserError> {
-    let mut buf = TokenBuffer::from_iter(tokens);
-    let mut parser = Parser::new();
-    parser.parse_stmt(&mut buf)
+fn case_label_conflict(label: &CaseLabel, other: &CaseLabel, token: &Token) -> Option<ParserError> {
+    let (start, end) = label.bounds();
+    let (other_start, other_end) = other.bounds();
+    if let (CaseLabel::Value(_), CaseLabel::Value(_)) = (label, other) {
+        return match compare_literals(start, other_start) {
+            Some(Ordering::Equal) => Some(ParserError::DuplicateCaseLabel(token.clone())),
+            _ => None,
+        };
+    }
+    let overlaps = compare_literals(start, other_end).is_some_and(Ordering::is_le)
+        && compare_literals(other_start, end).is_some_and(Ordering::is_le);
+    overlaps.then(|| ParserError::OverlappingCaseLabel(token.clone()))
+}
+
+/// Scans `source`, keeping its errors alongside those of the parser.
+fn scan(
+    source: &str,
+    options: ScannerOptions,
+    errors: &mut Vec<ParserError>,
+    warnings: &mut Vec<ScannerWarning>,
+) -> TokenBuffer {
+    let (tokens, scanner_errors, scanner_warnings) = scanner::scan_with_options(source, options);
+    errors.extend(scanner_errors.into_iter().map(ParserError::Scanner));
+    warnings.extend(scanner_warnings);
+    TokenBuffer::from_iter(tokens)
+}
+
+/// Parses a whole program, recovering from syntax errors so that all of them are reported.
+/// Statements containing errors are left out of the program, as are any tokens after the last
+/// statement, which are reported as unexpected.
+pub fn parse_program(source: &str) -> (Program, Vec<ParserError>) {
+    let (program, errors, _) = parse_program_with_options(source, ScannerOptions::default());
+    (program, errors)
 }
 
-pub fn parse_block(tokens: impl IntoIterator<Item = Token>) -> Block {
-    let mut buf = TokenBuffer::from_iter(tokens);
+/// Parses a whole program as `parse_program` does, scanning it with `options`, and also returns
+/// the warnings the scanner gave.
+pub fn parse_program_with_options(
+    source: &str,
+    options: ScannerOptions,
+) -> (Program, Vec<ParserError>, Vec<ScannerWarning>) {
     let mut parser = Parser::new();
-    parser.parse_block(&mut buf)
+    let mut errors = Vec::new();
+    let mut warnings = Vec::new();
+    let mut tokens = scan(source, options, &mut errors, &mut warnings);
+    let body = parser.parse_body(&mut tokens);
+    errors.append(&mut parser.errors);
+    let program = Program {
+        body,
+        symbols: parser.symbols,
+        source: source.to_string(),
+    };
+    (program, errors, warnings)
+}
+
+/// A parser that keeps its identifiers from one input to the next, so that a name has the same
+/// handle in each of them. This lets programs be parsed a piece at a time, as in a REPL.
+pub struct Session {
+    parser: Parser,
+    options: ScannerOptions,
+    warnings: Vec<ScannerWarning>,
+}
+
+impl Session {
+    pub fn new() -> Self {
+        Self::with_options(ScannerOptions::default())
+    }
+
+    /// Creates a session that scans each input with `options`.
+    pub fn with_options(options: ScannerOptions) -> Self {
+        Session {
+            parser: Parser::new(),
+            options,
+            warnings: Vec::new(),
+        }
+    }
+
+    /// Parses `source` as `parse_program` does, with the identifiers of earlier inputs.
+    pub fn parse_program(&mut self, source: &str) -> (Program, Vec<ParserError>) {
+        let mut errors = Vec::new();
+        let mut tokens = scan(source, self.options, &mut errors, &mut self.warnings);
+        let body = self.parser.parse_body(&mut tokens);
+        errors.append(&mut self.parser.errors);
+        let program = Program {
+            body,
+            symbols: self.parser.symbols.clone(),
+            source: source.to_string(),
+        };
+        (program, errors)
+    }
+
+    /// Parses an expression that makes up the whole of `source`.
+    pub fn parse_expression(&mut self, source: &str) -> Result<Expr, ParserError> {
+        let mut errors = Vec::new();
+        let mut tokens = scan(source, self.options, &mut errors, &mut self.warnings);
+        if let Some(error) = errors.into_iter().next() {
+            return Err(error);
+        }
+        let expr = self.parser.parse_expression(&mut tokens)?;
+        match tokens.current_token() {
+            Some(token) => Err(ParserError::UnexpectedToken(token.clone())),
+            None => Ok(expr),
+        }
+    }
+
+    /// The names of the identifiers seen so far.
+    pub fn symbols(&self) -> &SymbolTable {
+        &self.parser.symbols
+    }
+
+    /// Returns the warnings the scanner gave for the inputs parsed since this was last called.
+    pub fn take_warnings(&mut self) -> Vec<ScannerWarning> {
+        std::mem::take(&mut self.warnings)
+    }
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parses a single statement, failing with the first error in it.
+    fn parse_statement(source: &str) -> Result<Stmt, ParserError> {
+        let (tokens, errors) = scanner::scan(source);
+        assert!(errors.is_empty(), "{errors:?}");
+        let mut parser = Parser::new();
+        let stmt = parser.parse_stmt(&mut TokenBuffer::from_iter(tokens))?;
+        match parser.errors.into_iter().next() {
+            Some(error) => Err(error),
+            None => Ok(stmt),
+        }
+    }
+
+    fn parse_source(source: &str) -> Result<StmtKind, ParserError> {
+        parse_statement(source).map(|stmt| stmt.kind)
+    }
+
+    fn parse_expression(source: &str) -> Result<Expr, ParserError> {
+        Session::new().parse_expression(source)
+    }
+
+    #[test]
+    fn case_of() -> Result<(), ParserError> {
+        let source = "
+            CASE OF Grade
+                'A' : OUTPUT \"Excellent\"
+                      OUTPUT \"Well done\"
+                'B' TO 'D' : OUTPUT \"Pass\"
+                OTHERWISE : OUTPUT \"Fail\"
+            ENDCASE
+        ";
+        let StmtKind::CaseOf { cases, otherwise, .. } = parse_source(source)? else {
+            panic!("expected CASE statement");
+        };
+        assert_eq!(cases.len(), 2);
+        assert!(matches!(cases[0], (CaseLabel::Value(Literal::Char('A')), ref body) if body.contents.len() == 2));
+        assert!(matches!(cases[1].0, CaseLabel::Range(Literal::Char('B'), Literal::Char('D'))));
+        assert!(otherwise.is_some_and(|block| block.contents.len() == 1));
+        Ok(())
+    }
+
+    #[test]
+    fn call_statement() -> Result<(), ParserError> {
+        let StmtKind::Procedure { args, .. } = parse_source("CALL Swap(A, B[1])")? else {
+            panic!("expected CALL statement");
+        };
+        assert_eq!(args.map(|args| args.len()), Some(2));
+        assert!(matches!(parse_source("CALL Greet")?, StmtKind::Procedure { args: None, .. }));
+        assert!(matches!(parse_source("CALL Greet()")?, StmtKind::Procedure { args: Some(args), .. } if args.is_empty()));
+        assert!(matches!(parse_source("Swap(A, B)"), Err(ParserError::MissingCall(_))));
+        assert!(matches!(parse_source("CALL Pet.Speak(2)")?, StmtKind::Procedure { args: Some(_), .. }));
+        // Methods need CALL too, as do procedures named without parentheses
+        let missing_call = |source| match parse_source(source) {
+            Err(ParserError::MissingCall(token)) => token.lexeme,
+            result => panic!("expected a missing CALL, found {result:?}"),
+        };
+        assert_eq!(&*missing_call("Pet.Speak(2)"), "Pet.Speak");
+        assert_eq!(&*missing_call("SUPER.NEW(Name)"), "SUPER.NEW");
+        assert_eq!(&*missing_call("Greet"), "Greet");
+        let (_, errors) = parse_program("Greet\nOUTPUT 1");
+        assert!(matches!(&errors[..], [ParserError::MissingCall(token)] if token.span.start.line == 1), "{errors:?}");
+        assert!(matches!(parse_source("Total = 1"), Err(ParserError::UnexpectedToken(_))));
+        Ok(())
+    }
+
+    #[test]
+    fn parameter_modes() -> Result<(), ParserError> {
+        let source = "PROCEDURE P(A : INTEGER, BYREF B : INTEGER, C : REAL, BYVAL D : CHAR)\nENDPROCEDURE";
+        let StmtKind::ProcedureDecl { params: Some(params), .. } = parse_source(source)? else {
+            panic!("expected PROCEDURE declaration");
+        };
+        let modes: Vec<_> = params.iter().map(|param| param.mode).collect();
+        assert_eq!(
+            modes,
+            [PassingMode::ByValue, PassingMode::ByReference, PassingMode::ByReference, PassingMode::ByValue]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn array_type() -> Result<(), ParserError> {
+        let StmtKind::VariableDecl { type_, .. } = parse_source("DECLARE Grid : ARRAY[1:R, 0:C * 2] OF CHAR")? else {
+            panic!("expected DECLARE statement");
+        };
+        let Type::Array(ArrayType { inner_type, ranges }) = type_ else {
+            panic!("expected ARRAY type");
+        };
+        assert!(matches!(*inner_type, Type::Primitive(PrimitiveType::Char)));
+        assert_eq!(ranges.len(), 2);
+        assert!(matches!((&ranges[1].0.kind, &ranges[1].1.kind), (ExprKind::Literal(Literal::Integer(0)), ExprKind::Binary { .. })));
+        assert!(parse_source("DECLARE A : ARRAY[1:3] OF ARRAY[1:3] OF INTEGER").is_err());
+        assert!(parse_source("DECLARE A : ARRAY[] OF INTEGER").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn record_type() -> Result<(), ParserError> {
+        let source = "
+            TYPE StudentRecord
+                DECLARE Name : STRING
+                DECLARE Marks : ARRAY[1:3] OF INTEGER
+                DECLARE Tutor : TeacherRecord
+            ENDTYPE
+        ";
+        let StmtKind::RecordDecl { fields, .. } = parse_source(source)? else {
+            panic!("expected TYPE declaration");
+        };
+        assert_eq!(fields.len(), 3);
+        assert!(matches!(fields[2].type_, Type::Named(Expr { kind: ExprKind::Identifier { .. }, .. })));
+
+        let StmtKind::Assignment { target, value } = parse_source("Students[I].Name <- Student.Tutor.Name")? else {
+            panic!("expected assignment");
+        };
+        assert!(matches!(target.kind, ExprKind::FieldAccess { object, .. } if matches!(object.kind, ExprKind::ArrayIndex { .. })));
+        assert!(matches!(value.kind, ExprKind::FieldAccess { object, .. } if matches!(object.kind, ExprKind::FieldAccess { .. })));
+        Ok(())
+    }
+
+    #[test]
+    fn enumerated_and_pointer_types() -> Result<(), ParserError> {
+        let source = "TYPE Season = (Spring, Summer, Autumn, Winter)";
+        let StmtKind::TypeDecl { type_: Type::Enumerated(values), .. } = parse_source(source)? else {
+            panic!("expected enumerated type");
+        };
+        assert_eq!(values.len(), 4);
+
+        let StmtKind::TypeDecl { type_, .. } = parse_source("TYPE IntPtr = ^INTEGER")? else {
+            panic!("expected pointer type");
+        };
+        assert!(matches!(type_, Type::Pointer(inner) if matches!(*inner, Type::Primitive(PrimitiveType::Integer))));
+
+        let StmtKind::Assignment { target, value } = parse_source("Node^.Next <- ^Nodes[2]")? else {
+            panic!("expected assignment");
+        };
+        assert!(matches!(target.kind, ExprKind::FieldAccess { object, .. } if matches!(object.kind, ExprKind::Dereference { .. })));
+        assert!(matches!(value.kind, ExprKind::AddressOf { target } if matches!(target.kind, ExprKind::ArrayIndex { .. })));
+
+        // A `^` ending a line dereferences, even when the next line starts with an operand
+        let (program, errors) = parse_program("Y <- P^\nN <- Y + 1");
+        assert!(errors.is_empty(), "{errors:?}");
+        assert_eq!(program.body.contents.len(), 2);
+        assert!(matches!(&program.body.contents[0].kind, StmtKind::Assignment { value, .. }
+            if matches!(value.kind, ExprKind::Dereference { .. })));
+        Ok(())
+    }
+
+    #[test]
+    fn class_declaration() -> Result<(), ParserError> {
+        let source = "
+            CLASS Cat INHERITS Pet
+                PRIVATE Lives : INTEGER
+                DECLARE Colour : STRING
+                PUBLIC PROCEDURE NEW(GivenName : STRING)
+                    CALL SUPER.NEW(GivenName)
+                ENDPROCEDURE
+                PRIVATE FUNCTION Age() RETURNS INTEGER
+                    RETURN 1
+                ENDFUNCTION
+            ENDCLASS
+        ";
+        let StmtKind::ClassDecl { parent, members, .. } = parse_source(source)? else {
+            panic!("expected CLASS declaration");
+        };
+        assert!(parent.is_some());
+        assert!(matches!(members[0], Member::Attribute { visibility: Visibility::Private, .. }));
+        assert!(matches!(members[1], Member::Attribute { visibility: Visibility::Public, .. }));
+        let Member::Method { decl: Stmt { kind: StmtKind::ProcedureDecl { name, body, .. }, .. }, .. } = &members[2] else {
+            panic!("expected constructor");
+        };
+        assert!(matches!(name.kind, ExprKind::Identifier { handle: CONSTRUCTOR, .. }));
+        let StmtKind::Procedure { name, .. } = &body.contents[0].kind else {
+            panic!("expected method call");
+        };
+        assert!(matches!(&name.kind, ExprKind::FieldAccess { object, .. } if matches!(object.kind, ExprKind::Super)));
+        assert!(matches!(members[3], Member::Method { visibility: Visibility::Private, .. }));
+
+        let StmtKind::Assignment { value, .. } = parse_source("MyCat <- NEW Cat(\"Tom\", 3)")? else {
+            panic!("expected assignment");
+        };
+        assert!(matches!(value.kind, ExprKind::New { args, .. } if args.len() == 2));
+        assert!(matches!(parse_source("CALL MyCat.Feed(2)")?, StmtKind::Procedure { name: Expr { kind: ExprKind::FieldAccess { .. }, .. }, .. }));
+        Ok(())
+    }
+
+    #[test]
+    fn operator_precedence() -> Result<(), ParserError> {
+        let parse = |source: &str| parse_expression(source);
+        let binary = |expr: &Expr| match &expr.kind {
+            ExprKind::Binary { left, operator, right } => (left.clone(), *operator, right.clone()),
+            _ => panic!("expected binary expression, found {expr:?}"),
+        };
+
+        let (_, operator, right) = binary(&parse("1 + 2 * 3 MOD 4")?);
+        assert_eq!(operator, BinaryOperator::Plus);
+        let (left, operator, _) = binary(&right);
+        assert_eq!((binary(&left).1, operator), (BinaryOperator::Star, BinaryOperator::Mod));
+
+        let (_, operator, right) = binary(&parse("2 ^ 3 ^ 2")?);
+        assert_eq!((operator, binary(&right).1), (BinaryOperator::Power, BinaryOperator::Power));
+
+        let (_, operator, right) = binary(&parse("\"Total: \" & A + B")?);
+        assert_eq!((operator, binary(&right).1), (BinaryOperator::Concat, BinaryOperator::Plus));
+
+        let negated = parse("- X ^ 2")?;
+        assert!(matches!(negated.kind, ExprKind::Unary { operator: UnaryOperator::Negate, right }
+            if matches!(right.kind, ExprKind::Binary { operator: BinaryOperator::Power, .. })));
+
+        let (left, operator, right) = binary(&parse("X ^ -Y")?);
+        assert_eq!(operator, BinaryOperator::Power);
+        assert!(matches!(left.kind, ExprKind::Identifier { .. }));
+        assert!(matches!(right.kind, ExprKind::Unary { operator: UnaryOperator::Negate, .. }));
+        let (_, operator, right) = binary(&parse("X ^ NOT Y")?);
+        assert!(operator == BinaryOperator::Power && matches!(right.kind, ExprKind::Unary { .. }));
+        let (left, operator, _) = binary(&parse("P^ - Y")?);
+        assert!(operator == BinaryOperator::Minus && matches!(left.kind, ExprKind::Dereference { .. }));
+        let (left, operator, _) = binary(&parse("P^ -1")?);
+        assert!(operator == BinaryOperator::Minus && matches!(left.kind, ExprKind::Dereference { .. }));
+        let (_, operator, _) = binary(&parse("X^-1")?);
+        assert_eq!(operator, BinaryOperator::Power);
+
+        let (left, operator, right) = binary(&parse("P^ = Q^")?);
+        assert_eq!(operator, BinaryOperator::Equal);
+        assert!(matches!((left.kind, right.kind), (ExprKind::Dereference { .. }, ExprKind::Dereference { .. })));
+        Ok(())
+    }
+
+    #[test]
+    fn case_label_conflicts() {
+        let duplicate = "CASE OF X\n 1 : OUTPUT 1\n 1 : OUTPUT 2\n ENDCASE";
+        assert!(matches!(parse_source(duplicate), Err(ParserError::DuplicateCaseLabel(_))));
+        let overlapping = "CASE OF X\n 1 TO 10 : OUTPUT 1\n 10 TO 20 : OUTPUT 2\n ENDCASE";
+        assert!(matches!(parse_source(overlapping), Err(ParserError::OverlappingCaseLabel(_))));
+        let contained = "CASE OF X\n 5 : OUTPUT 1\n 1 TO 10 : OUTPUT 2\n ENDCASE";
+        assert!(matches!(parse_source(contained), Err(ParserError::OverlappingCaseLabel(_))));
+        let unterminated = "CASE OF X\n 1 : OUTPUT 1\n";
+        assert!(matches!(
+            parse_source(unterminated),
+            Err(ParserError::UnclosedBlock { expected: TokenType::EndCase, .. })
+        ));
+    }
+
+    #[test]
+    fn error_recovery() {
+        let source = "
+            DECLARE X : INTEGER
+            X <- 1 +
+            IF X > THEN
+                OUTPUT *
+            ENDIF
+            WHILE X < 10 DO
+                X <- X + 1
+            ENDIF
+            FOR I <- 1 TO 3
+                OUTPUT I
+            NEXT I
+            OUTPUT X
+        ";
+        let (program, errors) = parse_program(source);
+        let lines: Vec<_> = errors.iter().map(|error| error.span().start.line).collect();
+        assert_eq!(lines, [4, 4, 5, 9, 9], "{errors:?}");
+        assert!(matches!(&errors[3], ParserError::UnclosedBlock { expected: TokenType::EndWhile, opener, .. }
+            if opener.span.start.line == 7));
+        assert!(matches!(&errors[4], ParserError::UnexpectedToken(token) if token.type_ == TokenType::EndIf));
+        let kinds: Vec<_> = program.body.contents.iter().map(|stmt| &stmt.kind).collect();
+        assert!(matches!(
+            kinds[..],
+            [StmtKind::VariableDecl { .. }, StmtKind::While { .. }, StmtKind::ForLoop { .. }, StmtKind::Output(_)]
+        ));
+
+        let (_, errors) = parse_program("IF X THEN\n OUTPUT X");
+        assert!(matches!(&errors[..], [ParserError::UnclosedBlock { expected: TokenType::EndIf, .. }]));
+    }
+
+    #[test]
+    fn spans() -> Result<(), ParserError> {
+        let source = "IF Total >= 10 THEN\n    OUTPUT \"Ten\"\nENDIF";
+        let stmt = parse_statement(source)?;
+        assert_eq!((stmt.span.start.offset, stmt.span.end.offset), (0, source.len()));
+        let StmtKind::If { condition, then_branch, .. } = stmt.kind else {
+            panic!("expected IF statement");
+        };
+        assert_eq!(&source[condition.span.start.offset..condition.span.end.offset], "Total >= 10");
+        let output = &then_branch.contents[0];
+        assert_eq!((output.span.start.line, output.span.start.column), (2, 5));
+        assert_eq!(&source[output.span.start.offset..output.span.end.offset], "OUTPUT \"Ten\"");
+
+        let call = parse_expression("  Max(A[1], -B)")?;
+        assert_eq!((call.span.start.offset, call.span.end.offset), (2, 15));
+        let Err(ParserError::UnexpectedEOF(span)) = parse_statement("X <-") else {
+            panic!("expected end of input");
+        };
+        assert_eq!(span.start.offset, 4);
+        Ok(())
+    }
+
+    #[test]
+    fn program() {
+        let source = "DECLARE Total : INTEGER\nTotal <- 1\nENDIF\n";
+        let (program, errors) = parse_program(source);
+        assert!(matches!(&errors[..], [ParserError::UnexpectedToken(token)] if token.type_ == TokenType::EndIf));
+        assert_eq!(program.source, source);
+        let StmtKind::Assignment { target, .. } = &program.body.contents[1].kind else {
+            panic!("expected assignment");
+        };
+        let ExprKind::Identifier { handle, .. } = target.kind else {
+            panic!("expected identifier");
+        };
+        assert_eq!(program.symbols.name(handle), Some("Total"));
+        assert_eq!(program.symbols.handle("Total"), Some(handle));
+        assert_eq!(program.symbols.name(CONSTRUCTOR), Some("NEW"));
+
+        let (_, errors) = parse_program("OUTPUT \"Hello");
+        assert!(matches!(&errors[0], ParserError::Scanner(ScannerError::UnterminatedString(_))));
+    }
+
+    #[test]
+    fn scanner_options() {
+        let source = "declare x : integer\nx <- 1";
+        assert!(!parse_program(source).1.is_empty());
+        let options = ScannerOptions {
+            case_insensitive_keywords: true,
+            ..ScannerOptions::default()
+        };
+        let (program, errors, warnings) = parse_program_with_options(source, options);
+        assert!(errors.is_empty(), "{errors:?}");
+        assert_eq!(program.body.contents.len(), 2);
+        assert_eq!(warnings.len(), 2);
+
+        let mut session = Session::with_options(options);
+        assert!(session.parse_expression("x mod 2").is_ok());
+        assert!(matches!(&session.take_warnings()[..], [ScannerWarning::KeywordCase { .. }]));
+        assert!(session.take_warnings().is_empty());
+
+        let options = ScannerOptions {
+            suggest_ascii: true,
+            ..ScannerOptions::default()
+        };
+        let (_, errors, warnings) = parse_program_with_options("DECLARE X : INTEGER\nX ← 1", options);
+        assert!(errors.is_empty(), "{errors:?}");
+        assert!(matches!(&warnings[..], [ScannerWarning::NonAsciiSymbol { expected: "<-", .. }]));
+    }
 }
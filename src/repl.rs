@@ -0,0 +1,371 @@
+//! An interactive session that runs statements and evaluates expressions as they are typed.
+
+use std::io::{self, BufRead, Write};
+use std::slice;
+
+use rustyline::error::ReadlineError;
+use rustyline::DefaultEditor;
+
+use cambridgescript::ast::{Block, Program, Stmt, StmtKind};
+use cambridgescript::diagnostics::{self, Diagnostic, Severity};
+use cambridgescript::interpreter::{Interpreter, RuntimeError, Value};
+use cambridgescript::parser::{ParserError, Session};
+use cambridgescript::scanner::{self, ScannerOptions};
+
+const HELP: &str = "\
+Type a statement to run it, or an expression to see its value. Blocks such as IF … ENDIF may
+span several lines; enter an empty line to stop early. In a terminal, the up and down arrows
+recall earlier lines.
+
+  :tokens CODE   Print the tokens of CODE
+  :ast CODE      Print the syntax tree of CODE
+  :vars          List the global variables and their values
+  :history       List the entries run so far
+  :help          Print this message
+  :quit          Leave the REPL";
+
+/// Whether an entry was finished with, or needs more lines.
+#[derive(PartialEq)]
+enum Entry {
+    Complete,
+    Incomplete,
+}
+
+pub struct Repl<R, W> {
+    interpreter: Interpreter<R, W>,
+    session: Session,
+    history: Vec<String>,
+    options: ScannerOptions,
+    /// The statements of the entries run so far, which later entries are checked along with
+    statements: Vec<Stmt>,
+    /// The diagnostics for `statements`, so that only the ones a new entry adds are reported
+    known: Vec<Diagnostic>,
+    editor: Option<DefaultEditor>,
+}
+
+impl<R: BufRead, W: Write> Repl<R, W> {
+    /// Creates a REPL that reads entries from `input`, which programs also read INPUT from, and
+    /// scans them with `options`.
+    pub fn new(input: R, output: W, options: ScannerOptions) -> Self {
+        Repl {
+            interpreter: Interpreter::new(input, output),
+            session: Session::with_options(options),
+            history: Vec::new(),
+            options,
+            statements: Vec::new(),
+            known: Vec::new(),
+            editor: None,
+        }
+    }
+
+    /// Reads entries through a line editor that recalls earlier lines, for use in a terminal.
+    /// Programs still read INPUT from the input the REPL was created with. Lines are read plainly
+    /// if the terminal cannot be edited.
+    pub fn with_line_editor(mut self) -> Self {
+        self.editor = DefaultEditor::new().ok();
+        self
+    }
+
+    /// Reads the next line after showing `prompt`, returning `None` at the end of the input.
+    fn read_line(&mut self, prompt: &str) -> Result<Option<String>, ReadlineError> {
+        if let Some(editor) = &mut self.editor {
+            return match editor.readline(prompt) {
+                Ok(line) => {
+                    editor.add_history_entry(&line)?;
+                    Ok(Some(line + "\n"))
+                }
+                Err(ReadlineError::Eof) => Ok(None),
+                Err(error) => Err(error),
+            };
+        }
+        write!(self.interpreter.output(), "{prompt}")?;
+        self.interpreter.output().flush()?;
+        let mut line = String::new();
+        match self.interpreter.input().read_line(&mut line)? {
+            0 => Ok(None),
+            _ => Ok(Some(line)),
+        }
+    }
+
+    pub fn run(&mut self) -> io::Result<()> {
+        let mut entry = String::new();
+        loop {
+            let prompt = if entry.is_empty() { "> " } else { ". " };
+            let line = match self.read_line(prompt) {
+                Ok(Some(line)) => line,
+                Ok(None) => {
+                    if !entry.is_empty() {
+                        self.submit(&entry, true)?;
+                    }
+                    writeln!(self.interpreter.output())?;
+                    return Ok(());
+                }
+                // Ctrl-C abandons the entry being typed
+                Err(ReadlineError::Interrupted) => {
+                    entry.clear();
+                    continue;
+                }
+                Err(ReadlineError::Io(error)) => return Err(error),
+                Err(error) => return Err(io::Error::other(error)),
+            };
+            if entry.is_empty() {
+                match line.trim() {
+                    "" => continue,
+                    command if command.starts_with(':') => {
+                        if !self.command(command)? {
+                            return Ok(());
+                        }
+                        continue;
+                    }
+                    _ => {}
+                }
+            }
+            // An empty line ends an entry, even if it is incomplete
+            let force = line.trim().is_empty();
+            entry += &line;
+            if self.submit(&entry, force)? == Entry::Complete {
+                self.history.push(entry.trim_end().to_string());
+                entry.clear();
+            }
+        }
+    }
+
+    /// Runs a meta-command, returning false if the REPL should stop.
+    fn command(&mut self, command: &str) -> io::Result<bool> {
+        let (name, code) = command.split_once(' ').unwrap_or((command, ""));
+        match name {
+            ":tokens" => {
+                let (tokens, errors, warnings) = scanner::scan_with_options(code, self.options);
+                crate::write_tokens(self.interpreter.output(), &tokens)?;
+                let mut diagnostics: Vec<Diagnostic> = errors.iter().map(Diagnostic::from).collect();
+                diagnostics.extend(warnings.iter().map(Diagnostic::from));
+                self.report(diagnostics, code)?;
+            }
+            ":ast" => match self.session.parse_expression(code) {
+                Ok(expr) => write!(self.interpreter.output(), "{}", expr.tree(self.session.symbols()))?,
+                Err(_) => {
+                    let (program, errors) = self.session.parse_program(code);
+                    write!(self.interpreter.output(), "{}", program.body.tree(&program.symbols))?;
+                    self.report(errors.iter().map(Diagnostic::from).collect(), code)?;
+                }
+            },
+            ":vars" => {
+                let mut globals: Vec<(&str, String)> = self
+                    .interpreter
+                    .globals()
+                    .map(|(handle, value)| {
+                        let name = self.session.symbols().name(handle).unwrap_or("?");
+                        (name, value.map_or("(no value)".to_string(), Value::to_string))
+                    })
+                    .collect();
+                globals.sort();
+                let lines: Vec<String> = globals.iter().map(|(name, value)| format!("{name} = {value}")).collect();
+                for line in lines {
+                    writeln!(self.interpreter.output(), "{line}")?;
+                }
+            }
+            ":history" => {
+                for (number, entry) in self.history.iter().enumerate() {
+                    writeln!(self.interpreter.output(), "{:>3}  {}", number + 1, entry.replace('\n', "\n     "))?;
+                }
+            }
+            ":help" => writeln!(self.interpreter.output(), "{HELP}")?,
+            ":quit" | ":q" => return Ok(false),
+            _ => writeln!(self.interpreter.output(), "unknown command {name}; type :help for a list")?,
+        }
+        Ok(true)
+    }
+
+    /// Evaluates `entry` if it is an expression, or runs it as statements otherwise. Unless
+    /// `force` is set, an entry that is cut off part way is left to be continued.
+    fn submit(&mut self, entry: &str, force: bool) -> io::Result<Entry> {
+        let expression = self.session.parse_expression(entry);
+        // The entry is scanned again below if it is not an expression, so its warnings are only
+        // kept from one of the two
+        let warnings = self.session.take_warnings();
+        if let Ok(expr) = &expression {
+            // An expression is checked as the statement that would show its value
+            let output = Stmt {
+                kind: StmtKind::Output(vec![expr.clone()]),
+                span: expr.span,
+            };
+            let mut diagnostics: Vec<Diagnostic> = warnings.iter().map(Diagnostic::from).collect();
+            diagnostics.extend(self.analyse(slice::from_ref(&output)).0);
+            if !self.report(diagnostics, entry)? {
+                return Ok(Entry::Complete);
+            }
+            match self.interpreter.evaluate_expression(expr) {
+                Ok(value) => writeln!(self.interpreter.output(), "{value}")?,
+                Err(error) => self.report_runtime(&error, entry)?,
+            }
+            return Ok(Entry::Complete);
+        }
+
+        let (program, errors) = self.session.parse_program(entry);
+        let warnings = self.session.take_warnings();
+        let incomplete =
+            expression.is_err_and(|error| error.is_incomplete()) || errors.iter().any(ParserError::is_incomplete);
+        if incomplete && !force {
+            return Ok(Entry::Incomplete);
+        }
+        let mut diagnostics: Vec<Diagnostic> = errors.iter().map(Diagnostic::from).collect();
+        diagnostics.extend(warnings.iter().map(Diagnostic::from));
+        // Checks on the meaning of an entry rely on it having been parsed in full
+        let mut known = Vec::new();
+        if errors.is_empty() {
+            let (added, all) = self.analyse(&program.body.contents);
+            diagnostics.extend(added);
+            known = all;
+        }
+        if !self.report(diagnostics, entry)? {
+            return Ok(Entry::Complete);
+        }
+        self.statements.extend(program.body.contents.iter().cloned());
+        self.known = known;
+        if let Err(error) = self.interpreter.run(&program.body) {
+            self.report_runtime(&error, entry)?;
+        }
+        Ok(Entry::Complete)
+    }
+
+    /// Checks the entries run so far followed by `statements`, returning the diagnostics that
+    /// `statements` add and then all of them.
+    fn analyse(&self, statements: &[Stmt]) -> (Vec<Diagnostic>, Vec<Diagnostic>) {
+        let mut program = Program {
+            body: Block {
+                contents: self.statements.iter().chain(statements).cloned().collect(),
+            },
+            symbols: self.session.symbols().clone(),
+            source: String::new(),
+        };
+        let all = crate::analyse(&mut program);
+        // Earlier entries keep their diagnostics, so each known one accounts for one of the new set
+        let mut known = self.known.clone();
+        let added = all
+            .iter()
+            .filter(|diagnostic| match known.iter().position(|other| other == *diagnostic) {
+                Some(index) => {
+                    known.swap_remove(index);
+                    false
+                }
+                None => true,
+            })
+            .cloned()
+            .collect();
+        (added, all)
+    }
+
+    fn report_runtime(&mut self, error: &RuntimeError, entry: &str) -> io::Result<()> {
+        let span = self.interpreter.error_span().expect("failed statements are recorded");
+        let diagnostic = Diagnostic::runtime(error, self.session.symbols(), span);
+        self.report(vec![diagnostic], entry)?;
+        Ok(())
+    }
+
+    /// Writes `diagnostics`, returning whether the entry they are for may still be run, which it
+    /// may unless one of them is an error.
+    fn report(&mut self, diagnostics: Vec<Diagnostic>, source: &str) -> io::Result<bool> {
+        for diagnostic in &diagnostics {
+            write!(self.interpreter.output(), "{}", diagnostics::render(diagnostic, source, "<repl>"))?;
+        }
+        Ok(diagnostics.iter().all(|diagnostic| diagnostic.severity != Severity::Error))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(input: &str) -> String {
+        let mut output = Vec::new();
+        Repl::new(input.as_bytes(), &mut output, ScannerOptions::default()).run().unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn entries() {
+        let output = session(
+            "DECLARE X : INTEGER\nX <- 6\nX * 7\nIF X > 5 THEN\nOUTPUT \"big\"\nENDIF\n:vars\nY <-\n\n:history\n",
+        );
+        let expected = "\
+> > > 42
+> . . big
+> X = 6
+> . error[E102]: unexpected end of file
+ --> <repl>:1:5
+  |
+1 | Y <-
+  |     ^
+>   1  DECLARE X : INTEGER
+  2  X <- 6
+  3  X * 7
+  4  IF X > 5 THEN
+     OUTPUT \"big\"
+     ENDIF
+  5  Y <-
+> \n";
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn input_is_shared() {
+        let output = session("DECLARE Name : STRING\nINPUT Name\nAda\nName & \"!\"\n:quit\nX\n");
+        assert_eq!(output, "> > > Ada!\n> ");
+    }
+
+    #[test]
+    fn scanner_options() {
+        let mut output = Vec::new();
+        let options = ScannerOptions {
+            case_insensitive_keywords: true,
+            ..ScannerOptions::default()
+        };
+        Repl::new("output 1\n".as_bytes(), &mut output, options).run().unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.starts_with("> warning[W001]: keywords are written in capitals, as OUTPUT\n"), "{output}");
+        assert!(output.ends_with("1\n> \n"), "{output}");
+    }
+
+    #[test]
+    fn runtime_errors() {
+        let output = session("DECLARE Count : INTEGER\nCount + 1\nOUTPUT 1 DIV 0\n");
+        let expected = "\
+> > warning[W005]: this variable may not have been given a value yet, since its declaration on line 1
+ --> <repl>:1:1
+  |
+1 | Count + 1
+  | ^^^^^
+error[E502]: Count has not been given a value
+ --> <repl>:1:1
+  |
+1 | Count + 1
+  | ^^^^^^^^^
+> error[E529]: division by zero
+ --> <repl>:1:1
+  |
+1 | OUTPUT 1 DIV 0
+  | ^^^^^^^^^^^^^^
+> \n";
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn analysis() {
+        // Entries are checked along with earlier ones, and are not run if the checks find errors
+        let output = session("DECLARE N : INTEGER\nN <- TRUE\nN <- 2\nN * 2\nWHILE N > 5 DO\nENDWHILE\n");
+        let expected = "\
+> > error[E201]: expected INTEGER, found BOOLEAN
+ --> <repl>:1:6
+  |
+1 | N <- TRUE
+  |      ^^^^
+> > 4
+> . warning[W004]: this condition never changes, as the loop assigns none of the variables it reads
+ --> <repl>:1:7
+  |
+1 | WHILE N > 5 DO
+  |       ^^^^^
+> \n";
+        assert_eq!(output, expected);
+    }
+}
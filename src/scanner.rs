@@ -1,3 +1,4 @@
+use std::fmt;
 use std::iter;
 use std::rc::Rc;
 use std::str;
@@ -9,6 +10,7 @@ pub enum TokenType {
 
     Procedure, EndProcedure,
     Function, Returns, EndFunction, Return,
+    ByRef, ByVal,
 
     If, Then, Else, EndIf,
     Case, Otherwise, EndCase,
@@ -17,22 +19,27 @@ pub enum TokenType {
     While, Do, EndWhile,
 
     Declare, Constant,
+    Type, EndType,
+    Class, Inherits, EndClass,
+    Public, Private, New, Super,
     Input, Output, Call,
 
     OpenFile, ReadFile, WriteFile, CloseFile,
-    Read, Write,
+    Read, Write, Append, Random,
+    Seek, GetRecord, PutRecord,
 
     Integer, Real, Char, String, Boolean,
     Array, Of,
 
+    Div, Mod,
     And, Or, Not,
 
     // Symbols
 
     LParen, RParen, LBracket, RBracket,
-    Plus, Minus, Star, Slash, Caret,
+    Plus, Minus, Star, Slash, Caret, Ampersand,
     Equal, NotEqual, LessEqual, GreaterEqual, Less, Greater,
-    Comma, Colon, LArrow,
+    Comma, Colon, Dot, LArrow,
 
     // Others
 
@@ -47,15 +54,22 @@ pub enum TokenType {
     Whitespace, Comment,
 }
 
-#[derive(Copy, Clone, Debug)]
+/// A position in source code. The offset is in bytes, while lines and columns count characters
+/// from 1.
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct Location {
-    line: u32,
-    column: u32,
+    pub offset: usize,
+    pub line: u32,
+    pub column: u32,
 }
 
 impl Location {
-    fn new() -> Self {
-        Self { line: 1, column: 1 }
+    pub(crate) fn new() -> Self {
+        Self {
+            offset: 0,
+            line: 1,
+            column: 1,
+        }
     }
 
     fn increment_column(&mut self) {
@@ -68,33 +82,105 @@ impl Location {
     }
 }
 
+/// The range of source code between two locations, with `end` just past the last character.
+#[derive(Copy, Clone, PartialEq)]
+pub struct Span {
+    pub start: Location,
+    pub end: Location,
+}
+
+// Spans are attached to every node of the syntax tree, so they are kept short when debugging
+impl fmt::Debug for Span {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}..{}:{}",
+            self.start.line, self.start.column, self.end.line, self.end.column
+        )
+    }
+}
+
+impl Span {
+    /// The span from the start of `self` to the end of `other`.
+    pub fn to(self, other: Span) -> Span {
+        Span {
+            start: self.start,
+            end: other.end,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum ScannerError {
-    InvalidCharLiteral(Location),
-    UnterminatedString(Location),
-    InvalidRealLiteral(Location),
-    UnexpectedCharacter(char, Location),
+    InvalidCharLiteral(Span),
+    UnterminatedString(Span),
+    InvalidRealLiteral(Span),
+    UnexpectedCharacter(char, Span),
+    /// A number too large to be stored as an INTEGER or REAL
+    NumberTooLarge(Span),
+}
+
+#[derive(Debug)]
+pub enum ScannerWarning {
+    /// A keyword that was only recognised because keyword case is being ignored
+    KeywordCase { expected: Box<str>, span: Span },
+    /// A typographic symbol, such as `←` or a curly quote, that has a plain ASCII equivalent
+    NonAsciiSymbol { found: char, expected: &'static str, span: Span },
+}
+
+impl ScannerError {
+    /// The part of the source code the error refers to.
+    pub fn span(&self) -> Span {
+        match self {
+            ScannerError::InvalidCharLiteral(span)
+            | ScannerError::UnterminatedString(span)
+            | ScannerError::InvalidRealLiteral(span)
+            | ScannerError::UnexpectedCharacter(_, span)
+            | ScannerError::NumberTooLarge(span) => *span,
+        }
+    }
+}
+
+impl ScannerWarning {
+    pub fn span(&self) -> Span {
+        match self {
+            ScannerWarning::KeywordCase { span, .. } | ScannerWarning::NonAsciiSymbol { span, .. } => *span,
+        }
+    }
+}
+
+/// Settings that change what the scanner accepts.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ScannerOptions {
+    /// Recognise keywords written in any case, such as `endif`, with a warning for each one
+    pub case_insensitive_keywords: bool,
+    /// Warn about symbols such as `←` and `≠` that are accepted in place of their ASCII forms
+    pub suggest_ascii: bool,
 }
 
 #[derive(Clone, Debug)]
 pub struct Token {
     pub type_: TokenType,
     pub lexeme: Box<str>,
-    pub location: Location,
+    pub span: Span,
 }
 
 struct Scanner<'a> {
     source: iter::Peekable<str::Chars<'a>>,
     cur_lexeme: String,
     cur_location: Location,
+    options: ScannerOptions,
+    warnings: Vec<ScannerWarning>,
 }
 
 impl<'a> Scanner<'a> {
-    fn from_source(source: &'a str) -> Self {
+    fn from_source(source: &'a str, options: ScannerOptions) -> Self {
         Self {
             source: source.chars().peekable(),
             cur_lexeme: String::new(),
             cur_location: Location::new(),
+            options,
+            warnings: Vec::new(),
         }
     }
 
@@ -109,6 +195,7 @@ impl<'a> Scanner<'a> {
         let next = self.source.next();
         if let Some(c) = next {
             self.cur_lexeme.push(c);
+            self.cur_location.offset += c.len_utf8();
             if c == '\n' {
                 self.cur_location.increment_line();
             } else {
@@ -132,11 +219,19 @@ impl<'a> Scanner<'a> {
         }
     }
 
-    fn create_token(&mut self, type_: TokenType, location: Location) -> Token {
+    /// The span from `start` to the current location.
+    fn span_from(&self, start: Location) -> Span {
+        Span {
+            start,
+            end: self.cur_location,
+        }
+    }
+
+    fn create_token(&mut self, type_: TokenType, start: Location) -> Token {
         Token {
             type_,
             lexeme: self.cur_lexeme.clone().into_boxed_str(),
-            location,
+            span: self.span_from(start),
         }
     }
 
@@ -145,88 +240,85 @@ impl<'a> Scanner<'a> {
         TokenType::Comment
     }
 
-    fn char(&mut self) -> Result<TokenType, ScannerError> {
+    fn char(&mut self, close: char, start: Location) -> Result<TokenType, ScannerError> {
         let c = match self.advance() {
             Some(c) => c,
-            None => return Err(ScannerError::InvalidCharLiteral(self.cur_location)),
+            None => return Err(ScannerError::InvalidCharLiteral(self.span_from(start))),
         };
-        if !self.advance_if_match('\'') {
-            return Err(ScannerError::InvalidCharLiteral(self.cur_location));
+        if !self.advance_if_match(close) {
+            return Err(ScannerError::InvalidCharLiteral(self.span_from(start)));
         }
+        self.check_quotes("'", start);
         Ok(TokenType::CharLiteral(c))
     }
 
-    fn string(&mut self) -> Result<TokenType, ScannerError> {
-        self.advance_while(&|&c| c != '"' && c != '\n');
-        if !self.advance_if_match('"') {
-            return Err(ScannerError::UnterminatedString(self.cur_location));
+    fn string(&mut self, close: char, start: Location) -> Result<TokenType, ScannerError> {
+        self.advance_while(&|&c| c != close && c != '\n');
+        if !self.advance_if_match(close) {
+            return Err(ScannerError::UnterminatedString(self.span_from(start)));
         };
-        let content = self.cur_lexeme[1..self.cur_lexeme.len() - 1].to_string();
-        Ok(TokenType::StringLiteral(content.into()))
-    }
-
-    fn identifier(&mut self) -> TokenType {
-        self.advance_while(&char::is_ascii_alphabetic);
-        match self.cur_lexeme.as_str() {
-            "PROCEDURE" => TokenType::Procedure,
-            "ENDPROCEDURE" => TokenType::EndProcedure,
-            "FUNCTION" => TokenType::Function,
-            "RETURNS" => TokenType::Returns,
-            "ENDFUNCTION" => TokenType::EndFunction,
-            "RETURN" => TokenType::Return,
-            "IF" => TokenType::If,
-            "THEN" => TokenType::Then,
-            "ELSE" => TokenType::Else,
-            "ENDIF" => TokenType::EndIf,
-            "CASE" => TokenType::Case,
-            "OTHERWISE" => TokenType::Otherwise,
-            "ENDCASE" => TokenType::EndCase,
-            "FOR" => TokenType::For,
-            "TO" => TokenType::To,
-            "STEP" => TokenType::Step,
-            "NEXT" => TokenType::Next,
-            "REPEAT" => TokenType::Repeat,
-            "UNTIL" => TokenType::Until,
-            "WHILE" => TokenType::While,
-            "DO" => TokenType::Do,
-            "ENDWHILE" => TokenType::EndWhile,
-            "DECLARE" => TokenType::Declare,
-            "CONSTANT" => TokenType::Constant,
-            "INPUT" => TokenType::Input,
-            "OUTPUT" => TokenType::Output,
-            "CALL" => TokenType::Call,
-            "OPENFILE" => TokenType::OpenFile,
-            "READFILE" => TokenType::ReadFile,
-            "WRITEFILE" => TokenType::WriteFile,
-            "CLOSEFILE" => TokenType::CloseFile,
-            "READ" => TokenType::Read,
-            "WRITE" => TokenType::Write,
-            "INTEGER" => TokenType::Integer,
-            "REAL" => TokenType::Real,
-            "CHAR" => TokenType::Char,
-            "STRING" => TokenType::String,
-            "BOOLEAN" => TokenType::Boolean,
-            "ARRAY" => TokenType::Array,
-            "OF" => TokenType::Of,
-            "TRUE" => TokenType::BooleanLiteral(true),
-            "FALSE" => TokenType::BooleanLiteral(false),
-            "AND" => TokenType::And,
-            "OR" => TokenType::Or,
-            "NOT" => TokenType::Not,
-            identifier => TokenType::Identifier(identifier.into()),
+        self.check_quotes("\"", start);
+        let mut content = self.cur_lexeme.chars();
+        content.next();
+        content.next_back();
+        Ok(TokenType::StringLiteral(content.as_str().into()))
+    }
+
+    /// Warns about a literal in curly quotes, when asked to.
+    fn check_quotes(&mut self, expected: &'static str, start: Location) {
+        let mut quotes = [self.cur_lexeme.chars().next(), self.cur_lexeme.chars().next_back()].into_iter().flatten();
+        if let Some(found) = quotes.find(|c| !c.is_ascii()) {
+            self.suggest_ascii(found, expected, start);
+        }
+    }
+
+    /// Scans a typographic symbol as the token for its ASCII equivalent.
+    fn symbol(&mut self, found: char, type_: TokenType, expected: &'static str, start: Location) -> TokenType {
+        self.suggest_ascii(found, expected, start);
+        type_
+    }
+
+    fn suggest_ascii(&mut self, found: char, expected: &'static str, start: Location) {
+        if self.options.suggest_ascii {
+            let span = self.span_from(start);
+            self.warnings.push(ScannerWarning::NonAsciiSymbol { found, expected, span });
+        }
+    }
+
+    fn identifier(&mut self, start: Location) -> TokenType {
+        self.advance_while(&|&c| c.is_ascii_alphanumeric() || c == '_');
+        if let Some(keyword) = keyword(&self.cur_lexeme) {
+            return keyword;
+        }
+        if self.options.case_insensitive_keywords {
+            let uppercase = self.cur_lexeme.to_ascii_uppercase();
+            if let Some(keyword) = keyword(&uppercase) {
+                self.warnings.push(ScannerWarning::KeywordCase {
+                    expected: uppercase.into(),
+                    span: self.span_from(start),
+                });
+                return keyword;
+            }
         }
+        TokenType::Identifier(self.cur_lexeme.as_str().into())
     }
 
-    fn number(&mut self) -> Result<TokenType, ScannerError> {
+    fn number(&mut self, start: Location) -> Result<TokenType, ScannerError> {
         self.advance_while(&char::is_ascii_digit);
         if self.advance_if_match('.') {
             if !self.check_next(&char::is_ascii_digit) {
-                return Err(ScannerError::InvalidRealLiteral(self.cur_location));
+                return Err(ScannerError::InvalidRealLiteral(self.span_from(start)));
             }
             self.advance_while(&char::is_ascii_digit);
-            Ok(TokenType::RealLiteral(self.cur_lexeme.parse().unwrap()))
+            match self.cur_lexeme.parse::<f64>() {
+                Ok(r) if r.is_finite() => Ok(TokenType::RealLiteral(r)),
+                _ => Err(ScannerError::NumberTooLarge(self.span_from(start))),
+            }
         } else {
-            Ok(TokenType::IntegerLiteral(self.cur_lexeme.parse().unwrap()))
+            match self.cur_lexeme.parse() {
+                Ok(i) => Ok(TokenType::IntegerLiteral(i)),
+                Err(_) => Err(ScannerError::NumberTooLarge(self.span_from(start))),
+            }
         }
     }
 
@@ -236,26 +328,17 @@ impl<'a> Scanner<'a> {
     }
 
     fn scan_next(&mut self) -> Option<Result<Token, ScannerError>> {
-        let location = self.cur_location;
+        let start = self.cur_location;
         self.cur_lexeme.clear();
 
-        let next_char = match self.advance() {
-            Some(c) => c,
-            None => return None, // Already at end
-        };
+        let next_char = self.advance()?;
         let result = match next_char {
             '(' => Ok(TokenType::LParen),
             ')' => Ok(TokenType::RParen),
             '[' => Ok(TokenType::LBracket),
             ']' => Ok(TokenType::RBracket),
             '+' => Ok(TokenType::Plus),
-            '-' => {
-                if self.check_next(&char::is_ascii_digit) {
-                    self.number()
-                } else {
-                    Ok(TokenType::Minus)
-                }
-            }
+            '-' => Ok(TokenType::Minus),
             '*' => Ok(TokenType::Star),
             '/' => {
                 if self.advance_if_match('/') {
@@ -265,6 +348,7 @@ impl<'a> Scanner<'a> {
                 }
             }
             '^' => Ok(TokenType::Caret),
+            '&' => Ok(TokenType::Ampersand),
             '=' => Ok(TokenType::Equal),
             '>' => {
                 if self.advance_if_match('=') {
@@ -285,15 +369,113 @@ impl<'a> Scanner<'a> {
                 }
             }
             ',' => Ok(TokenType::Comma),
+            '.' => {
+                if self.check_next(&char::is_ascii_digit) {
+                    Err(ScannerError::InvalidRealLiteral(self.span_from(start)))
+                } else {
+                    Ok(TokenType::Dot)
+                }
+            }
             ':' => Ok(TokenType::Colon),
-            '\'' => self.char(),
-            '"' => self.string(),
-            c if c.is_ascii_alphabetic() => Ok(self.identifier()),
-            c if c.is_ascii_digit() => self.number(),
+            '←' => Ok(self.symbol(next_char, TokenType::LArrow, "<-", start)),
+            '≠' => Ok(self.symbol(next_char, TokenType::NotEqual, "<>", start)),
+            '≤' => Ok(self.symbol(next_char, TokenType::LessEqual, "<=", start)),
+            '≥' => Ok(self.symbol(next_char, TokenType::GreaterEqual, ">=", start)),
+            c if is_single_quote(&c) => self.char(closing_quote(c), start),
+            c if is_double_quote(&c) => self.string(closing_quote(c), start),
+            c if c.is_ascii_alphabetic() => Ok(self.identifier(start)),
+            c if c.is_ascii_digit() => self.number(start),
             c if c.is_ascii_whitespace() => Ok(self.whitespace()),
-            c => Err(ScannerError::UnexpectedCharacter(c, self.cur_location)),
+            c => Err(ScannerError::UnexpectedCharacter(c, self.span_from(start))),
         };
-        Some(result.map(|t| self.create_token(t, location)))
+        Some(result.map(|t| self.create_token(t, start)))
+    }
+}
+
+fn keyword(lexeme: &str) -> Option<TokenType> {
+    match lexeme {
+        "PROCEDURE" => Some(TokenType::Procedure),
+        "ENDPROCEDURE" => Some(TokenType::EndProcedure),
+        "FUNCTION" => Some(TokenType::Function),
+        "RETURNS" => Some(TokenType::Returns),
+        "ENDFUNCTION" => Some(TokenType::EndFunction),
+        "RETURN" => Some(TokenType::Return),
+        "BYREF" => Some(TokenType::ByRef),
+        "BYVAL" => Some(TokenType::ByVal),
+        "IF" => Some(TokenType::If),
+        "THEN" => Some(TokenType::Then),
+        "ELSE" => Some(TokenType::Else),
+        "ENDIF" => Some(TokenType::EndIf),
+        "CASE" => Some(TokenType::Case),
+        "OTHERWISE" => Some(TokenType::Otherwise),
+        "ENDCASE" => Some(TokenType::EndCase),
+        "FOR" => Some(TokenType::For),
+        "TO" => Some(TokenType::To),
+        "STEP" => Some(TokenType::Step),
+        "NEXT" => Some(TokenType::Next),
+        "REPEAT" => Some(TokenType::Repeat),
+        "UNTIL" => Some(TokenType::Until),
+        "WHILE" => Some(TokenType::While),
+        "DO" => Some(TokenType::Do),
+        "ENDWHILE" => Some(TokenType::EndWhile),
+        "DECLARE" => Some(TokenType::Declare),
+        "CONSTANT" => Some(TokenType::Constant),
+        "TYPE" => Some(TokenType::Type),
+        "ENDTYPE" => Some(TokenType::EndType),
+        "CLASS" => Some(TokenType::Class),
+        "INHERITS" => Some(TokenType::Inherits),
+        "ENDCLASS" => Some(TokenType::EndClass),
+        "PUBLIC" => Some(TokenType::Public),
+        "PRIVATE" => Some(TokenType::Private),
+        "NEW" => Some(TokenType::New),
+        "SUPER" => Some(TokenType::Super),
+        "INPUT" => Some(TokenType::Input),
+        "OUTPUT" => Some(TokenType::Output),
+        "CALL" => Some(TokenType::Call),
+        "OPENFILE" => Some(TokenType::OpenFile),
+        "READFILE" => Some(TokenType::ReadFile),
+        "WRITEFILE" => Some(TokenType::WriteFile),
+        "CLOSEFILE" => Some(TokenType::CloseFile),
+        "READ" => Some(TokenType::Read),
+        "WRITE" => Some(TokenType::Write),
+        "APPEND" => Some(TokenType::Append),
+        "RANDOM" => Some(TokenType::Random),
+        "SEEK" => Some(TokenType::Seek),
+        "GETRECORD" => Some(TokenType::GetRecord),
+        "PUTRECORD" => Some(TokenType::PutRecord),
+        "INTEGER" => Some(TokenType::Integer),
+        "REAL" => Some(TokenType::Real),
+        "CHAR" => Some(TokenType::Char),
+        "STRING" => Some(TokenType::String),
+        "BOOLEAN" => Some(TokenType::Boolean),
+        "ARRAY" => Some(TokenType::Array),
+        "OF" => Some(TokenType::Of),
+        "TRUE" => Some(TokenType::BooleanLiteral(true)),
+        "FALSE" => Some(TokenType::BooleanLiteral(false)),
+        "DIV" => Some(TokenType::Div),
+        "MOD" => Some(TokenType::Mod),
+        "AND" => Some(TokenType::And),
+        "OR" => Some(TokenType::Or),
+        "NOT" => Some(TokenType::Not),
+        _ => None,
+    }
+}
+
+/// Curly quotes are accepted in place of straight ones, and a literal ends at the quote matching
+/// the one it starts with, so that `"She said “hi”"` is a single string.
+fn is_single_quote(c: &char) -> bool {
+    matches!(c, '\'' | '‘' | '’')
+}
+
+fn is_double_quote(c: &char) -> bool {
+    matches!(c, '"' | '“' | '”')
+}
+
+fn closing_quote(open: char) -> char {
+    match open {
+        '“' => '”',
+        '‘' => '’',
+        c => c,
     }
 }
 
@@ -321,23 +503,40 @@ impl<'a> Iterator for TokenStream<'a> {
     }
 }
 
-pub fn iter_tokens(source: &str) -> TokenStream {
+impl TokenStream<'_> {
+    /// Warnings about the tokens scanned so far.
+    pub fn warnings(&self) -> &[ScannerWarning] {
+        &self.scanner.warnings
+    }
+}
+
+pub fn iter_tokens(source: &str) -> TokenStream<'_> {
+    iter_tokens_with_options(source, ScannerOptions::default())
+}
+
+pub fn iter_tokens_with_options(source: &str, options: ScannerOptions) -> TokenStream<'_> {
     TokenStream {
-        scanner: Scanner::from_source(source),
+        scanner: Scanner::from_source(source, options),
         ignore_irrelevant: true,
     }
 }
 
 pub fn scan(source: &str) -> (Vec<Token>, Vec<ScannerError>) {
+    let (tokens, errors, _) = scan_with_options(source, ScannerOptions::default());
+    (tokens, errors)
+}
+
+pub fn scan_with_options(source: &str, options: ScannerOptions) -> (Vec<Token>, Vec<ScannerError>, Vec<ScannerWarning>) {
     let mut tokens: Vec<Token> = Vec::new();
     let mut errors: Vec<ScannerError> = Vec::new();
-    for item in iter_tokens(source) {
+    let mut stream = iter_tokens_with_options(source, options);
+    for item in stream.by_ref() {
         match item {
             Ok(token) => tokens.push(token),
             Err(error) => errors.push(error),
         }
     }
-    (tokens, errors)
+    (tokens, errors, stream.scanner.warnings)
 }
 
 #[cfg(test)]
@@ -345,7 +544,7 @@ mod tests {
     use super::*;
 
     fn scan_single_token(source: &str) -> Result<Token, ScannerError> {
-        let mut scanner = Scanner::from_source(source);
+        let mut scanner = Scanner::from_source(source, ScannerOptions::default());
         scanner.scan_next().unwrap()
     }
 
@@ -362,6 +561,9 @@ mod tests {
     fn keyword_token() -> Result<(), ScannerError> {
         assert_token_type!("DECLARE", TokenType::Declare);
         assert_token_type!("ENDIF", TokenType::EndIf);
+        assert_token_type!("APPEND", TokenType::Append);
+        assert_token_type!("ENDCLASS", TokenType::EndClass);
+        assert_token_type!("MOD", TokenType::Mod);
         Ok(())
     }
 
@@ -371,16 +573,35 @@ mod tests {
         assert_token_type!("<", TokenType::Less);
         assert_token_type!("<=", TokenType::LessEqual);
         assert_token_type!("<>", TokenType::NotEqual);
+        assert_token_type!(".", TokenType::Dot);
         assert_token_type!("-", TokenType::Minus);
+        assert_token_type!("&", TokenType::Ampersand);
         Ok(())
     }
 
     #[test]
     fn identifier_token() -> Result<(), ScannerError> {
         assert_token_type!("foo", TokenType::Identifier(Rc::from("foo")));
+        assert_token_type!("Total1", TokenType::Identifier(Rc::from("Total1")));
+        assert_token_type!("max_value", TokenType::Identifier(Rc::from("max_value")));
+        assert_token_type!("endif", TokenType::Identifier(Rc::from("endif")));
+        assert!(matches!(scan_single_token("_value"), Err(ScannerError::UnexpectedCharacter('_', _))));
         Ok(())
     }
 
+    #[test]
+    fn case_insensitive_keywords() {
+        let options = ScannerOptions {
+            case_insensitive_keywords: true,
+            ..ScannerOptions::default()
+        };
+        let (tokens, errors, warnings) = scan_with_options("EndIf ENDIF endif_flag", options);
+        assert!(errors.is_empty());
+        let types: Vec<_> = tokens.into_iter().map(|token| token.type_).collect();
+        assert_eq!(types, [TokenType::EndIf, TokenType::EndIf, TokenType::Identifier(Rc::from("endif_flag"))]);
+        assert!(matches!(&warnings[..], [ScannerWarning::KeywordCase { expected, .. }] if &**expected == "ENDIF"));
+    }
+
     #[test]
     fn char_literal_token() -> Result<(), ScannerError> {
         assert_token_type!("'c'", TokenType::CharLiteral('c'));
@@ -400,9 +621,48 @@ mod tests {
         assert_token_type!(r#""hello world""#, TokenType::StringLiteral(Rc::from("hello world")));
         assert_token_type!(r#""\n\r\b""#, TokenType::StringLiteral(Rc::from(r"\n\r\b")));
         assert_token_type!(r#""\""#, TokenType::StringLiteral(Rc::from(r"\")));
+        // Only the quote matching the opening one ends a literal
+        assert_token_type!("\"She said “hello” ok\"", TokenType::StringLiteral(Rc::from("She said “hello” ok")));
+        assert_token_type!("“say \"hi\"”", TokenType::StringLiteral(Rc::from("say \"hi\"")));
+        assert_token_type!("'’'", TokenType::CharLiteral('’'));
         Ok(())
     }
 
+    #[test]
+    fn unicode_symbols() {
+        let source = "X ← “héllo” ≠ ‘a’ ≤ '≥'";
+        let (tokens, errors) = scan(source);
+        assert!(errors.is_empty(), "{errors:?}");
+        let types: Vec<_> = tokens.into_iter().map(|token| token.type_).collect();
+        assert_eq!(
+            types[1..],
+            [
+                TokenType::LArrow,
+                TokenType::StringLiteral(Rc::from("héllo")),
+                TokenType::NotEqual,
+                TokenType::CharLiteral('a'),
+                TokenType::LessEqual,
+                TokenType::CharLiteral('≥'),
+            ]
+        );
+
+        let options = ScannerOptions {
+            suggest_ascii: true,
+            ..ScannerOptions::default()
+        };
+        let (_, _, warnings) = scan_with_options(source, options);
+        let expected: Vec<_> = warnings
+            .iter()
+            .filter_map(|warning| match warning {
+                ScannerWarning::NonAsciiSymbol { expected, .. } => Some(*expected),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(expected, ["<-", "\"", "<>", "'", "<="]);
+        let (_, _, warnings) = scan_with_options("X ← 1", ScannerOptions::default());
+        assert!(warnings.is_empty());
+    }
+
     #[test]
     fn unterminated_string_literal() {
         assert!(matches!(scan_single_token(r#""hello"#), Err(ScannerError::UnterminatedString(_))))
@@ -411,15 +671,26 @@ mod tests {
     #[test]
     fn integer_literal_token() -> Result<(), ScannerError> {
         assert_token_type!("42", TokenType::IntegerLiteral(42));
-        assert_token_type!("-5", TokenType::IntegerLiteral(-5));
         Ok(())
     }
 
+    #[test]
+    fn minus_sign() {
+        let types = |source| scan(source).0.into_iter().map(|token| token.type_).collect::<Vec<_>>();
+        assert_eq!(
+            types("Count-1"),
+            [TokenType::Identifier(Rc::from("Count")), TokenType::Minus, TokenType::IntegerLiteral(1)]
+        );
+        assert_eq!(types("A[2]-1.5")[4..], [TokenType::Minus, TokenType::RealLiteral(1.5)]);
+        // The sign of a negative number is left to the parser, so that `-2 ^ 2` negates the power
+        assert_eq!(types("X <- -1")[2..], [TokenType::Minus, TokenType::IntegerLiteral(1)]);
+        assert_eq!(types("(2)*-3")[3..], [TokenType::Star, TokenType::Minus, TokenType::IntegerLiteral(3)]);
+    }
+
     #[test]
     fn real_literal_token() -> Result<(), ScannerError> {
         assert_token_type!("0.6", TokenType::RealLiteral(0.6));
         assert_token_type!("13.0", TokenType::RealLiteral(13.0));
-        assert_token_type!("-2.5", TokenType::RealLiteral(-2.5));
         Ok(())
     }
 
@@ -429,10 +700,36 @@ mod tests {
         assert!(scan_single_token(".5").is_err());
     }
 
+    #[test]
+    fn number_too_large() -> Result<(), ScannerError> {
+        assert_token_type!("9223372036854775807", TokenType::IntegerLiteral(i64::MAX));
+        assert!(matches!(scan_single_token("99999999999999999999"), Err(ScannerError::NumberTooLarge(_))));
+        let huge = format!("1{}.0", "0".repeat(400));
+        assert!(matches!(scan_single_token(&huge), Err(ScannerError::NumberTooLarge(_))));
+        Ok(())
+    }
+
     #[test]
     fn boolean_literal_token() -> Result<(), ScannerError> {
         assert_token_type!("TRUE", TokenType::BooleanLiteral(true));
         assert_token_type!("FALSE", TokenType::BooleanLiteral(false));
         Ok(())
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn spans() {
+        let source = "X ← “héllo”\n  OUTPUT X";
+        let (tokens, _) = scan(source);
+        let text = |span: Span| &source[span.start.offset..span.end.offset];
+        assert_eq!(text(tokens[1].span), "←");
+        assert_eq!(text(tokens[2].span), "“héllo”");
+        assert_eq!((tokens[2].span.start.column, tokens[2].span.end.column), (5, 12));
+        assert_eq!((tokens[3].span.start.line, tokens[3].span.start.column), (2, 3));
+
+        let (_, errors) = scan("A <- \"open");
+        let [ScannerError::UnterminatedString(span)] = errors[..] else {
+            panic!("expected unterminated string");
+        };
+        assert_eq!((span.start.offset, span.end.offset), (5, 10));
+    }
+}